@@ -0,0 +1,73 @@
+/// A cycle in the sequence `start, step(start), step(step(start)), ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle<T> {
+    /// Number of steps before the first state that is part of the cycle.
+    #[allow(unused, reason = "tests")]
+    pub offset: usize,
+    /// Number of steps it takes to get back to the same state.
+    pub length: usize,
+    /// The first state that is part of the cycle, which is also the first state to repeat.
+    pub first_repeat: T,
+}
+
+/// Finds the cycle of a deterministic transition function using Brent's variant of the
+/// tortoise and hare algorithm, which keeps only two states in memory. `step` advances a state in
+/// place, so the states are only copied when the tortoise catches up, and never reallocated.
+pub fn find_cycle<T: Clone + Eq>(start: &T, step: impl Fn(&mut T)) -> Cycle<T> {
+    let mut power = 1;
+    let mut length = 1;
+    let mut slow = start.clone();
+    let mut fast = start.clone();
+    step(&mut fast);
+    while slow != fast {
+        if power == length {
+            slow.clone_from(&fast);
+            power *= 2;
+            length = 0;
+        }
+        step(&mut fast);
+        length += 1;
+    }
+    slow.clone_from(start);
+    fast.clone_from(start);
+    for _ in 0..length {
+        step(&mut fast);
+    }
+    let mut offset = 0;
+    while slow != fast {
+        step(&mut slow);
+        step(&mut fast);
+        offset += 1;
+    }
+    Cycle {
+        offset,
+        length,
+        first_repeat: slow,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cycle() {
+        // 0, 1, 2, 3, 4, 5, 6, 2, ...
+        let cycle = find_cycle(&0, |n| *n = if *n == 6 { 2 } else { *n + 1 });
+        assert_eq!(
+            cycle,
+            Cycle {
+                offset: 2,
+                length: 5,
+                first_repeat: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_cycle_fixed_point() {
+        let cycle = find_cycle(&7, |_| {});
+        assert_eq!(cycle.offset, 0);
+        assert_eq!(cycle.length, 1);
+    }
+}
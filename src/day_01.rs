@@ -1,17 +1,36 @@
 use std::num::ParseIntError;
 
+use crate::solver::{Answer, Solver};
+
 #[aoc_generator(day1)]
-fn parse(input: &str) -> Result<Vec<u32>, ParseIntError> {
+fn parse(input: &str) -> Result<Vec<u64>, ParseIntError> {
     input.lines().map(str::parse).collect()
 }
 
 #[aoc(day1, part1)]
-fn part_1(masses: &[u32]) -> u32 {
+fn part_1(masses: &[u64]) -> u64 {
     masses.iter().map(|&m| (m / 3).saturating_sub(2)).sum()
 }
 
 #[aoc(day1, part2)]
-fn part_2(masses: &[u32]) -> u32 {
+fn part_2(masses: &[u64]) -> u64 {
+    fuel_breakdown(masses).into_iter().sum()
+}
+
+pub struct Day01;
+
+impl Solver for Day01 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
+}
+
+/// Recursive fuel requirement of each module, in input order.
+fn fuel_breakdown(masses: &[u64]) -> Vec<u64> {
     masses
         .iter()
         .map(|&m| {
@@ -23,7 +42,7 @@ fn part_2(masses: &[u32]) -> u32 {
             }
             fuel
         })
-        .sum()
+        .collect()
 }
 
 #[cfg(test)]
@@ -36,14 +55,20 @@ mod tests {
     #[test_case(&[1969] => 654)]
     #[test_case(&[100_756] => 33_583)]
     #[test_case(&[12, 14, 1969, 100_756] => 34_241)]
-    fn test_part_1(messes: &[u32]) -> u32 {
+    fn test_part_1(messes: &[u64]) -> u64 {
         part_1(messes)
     }
     #[test_case(&[14] => 2)]
     #[test_case(&[1969] => 966)]
     #[test_case(&[100_756] => 50346)]
     #[test_case(&[14, 1969, 100_756] => 51_314)]
-    fn test_part_2(messes: &[u32]) -> u32 {
+    fn test_part_2(messes: &[u64]) -> u64 {
         part_2(messes)
     }
+
+    #[test]
+    fn test_fuel_breakdown() {
+        let result = fuel_breakdown(&[14, 1969, 100_756]);
+        assert_eq!(result, [2, 966, 50_346]);
+    }
 }
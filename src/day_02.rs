@@ -1,6 +1,7 @@
 use std::num::ParseIntError;
 
 use crate::machine::{parse_program, Machine, Value};
+use crate::solver::{Answer, Solver};
 
 #[aoc_generator(day2)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
@@ -9,28 +10,97 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day2, part1)]
 fn part_1(program: &[Value]) -> Value {
-    let mut machine = Machine::new(program);
-    machine.write(1, 12);
-    machine.write(2, 2);
-    machine.run_until_stopped().unwrap();
-    machine.read(0)
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> Value {
+    run_with_inputs(program, 12, 2, trace)
 }
 
 #[aoc(day2, part2)]
-fn part_2(program: &[Value]) -> Value {
+fn part_2(program: &[Value]) -> Option<Value> {
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> Option<Value> {
+    search(program, 19_690_720, trace).map(|(noun, verb)| 100 * noun + verb)
+}
+
+#[derive(Default)]
+pub struct Day02 {
+    trace: bool,
+}
+
+impl Solver for Day02 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
+/// Runs the program with `noun` and `verb` patched in, and returns the value at address 0.
+fn run_with_inputs(program: &[Value], noun: Value, verb: Value, trace: bool) -> Value {
+    run_with_inputs_into_memory(program, noun, verb, trace)[0]
+}
+
+/// Runs the program with `noun` and `verb` patched in, and returns the final memory.
+fn run_with_inputs_into_memory(
+    program: &[Value],
+    noun: Value,
+    verb: Value,
+    trace: bool,
+) -> Vec<Value> {
+    let mut machine = Machine::new(program).with_log(trace);
+    run_patched(&mut machine, noun, verb);
+    machine.into_memory()
+}
+
+/// Patches `noun` and `verb` into the memory of `machine`, and runs it until it halts.
+fn run_patched(machine: &mut Machine, noun: Value, verb: Value) {
+    machine.write(1, noun);
+    machine.write(2, verb);
+    machine.run_until_stopped().unwrap();
+}
+
+/// First `(noun, verb)` pair in `0..=99` for which the program outputs `target`.
+fn search(program: &[Value], target: Value, trace: bool) -> Option<(Value, Value)> {
+    let mut machine = Machine::new(program).with_log(trace);
+    solutions(&mut machine, program, target).next()
+}
+
+/// All `(noun, verb)` pairs in `0..=99` for which the program outputs `target`, in order. Empty if
+/// there are none.
+#[allow(unused, reason = "tests")]
+fn find_all_solutions(program: &[Value], target: Value) -> Vec<(Value, Value)> {
     let mut machine = Machine::new(program);
-    for noun in 0..=99 {
-        for verb in 0..=99 {
+    solutions(&mut machine, program, target).collect()
+}
+
+/// The `(noun, verb)` pairs in `0..=99` for which the program outputs `target`, found by resetting
+/// and reusing `machine` for every pair.
+fn solutions<'a>(
+    machine: &'a mut Machine,
+    program: &'a [Value],
+    target: Value,
+) -> impl Iterator<Item = (Value, Value)> + 'a {
+    (0..=99)
+        .flat_map(|noun| (0..=99).map(move |verb| (noun, verb)))
+        .filter(move |&(noun, verb)| {
             machine.reset(program);
-            machine.write(1, noun);
-            machine.write(2, verb);
-            machine.run_until_stopped().unwrap();
-            if machine.read(0) == 19_690_720 {
-                return 100 * noun + verb;
-            }
-        }
-    }
-    0
+            run_patched(machine, noun, verb);
+            machine.read(0) == target
+        })
 }
 
 #[cfg(test)]
@@ -70,4 +140,29 @@ mod tests {
         // of applying noun 12 and verb 2 to the first example.
         assert_eq!(reuslt, 100);
     }
+
+    #[test]
+    fn test_run_with_inputs_into_memory() {
+        let program = parse(EXAMPLE1).unwrap();
+        let memory = run_with_inputs_into_memory(&program, 12, 2, false);
+        assert_eq!(memory, [100, 12, 2, 2, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert_eq!(memory[0], part_1(&program));
+    }
+
+    #[test]
+    fn test_find_all_solutions() {
+        let program = parse(EXAMPLE1).unwrap();
+        let result = find_all_solutions(&program, 100);
+        assert!(result.contains(&(12, 2)));
+        assert_eq!(result.first().copied(), search(&program, 100, false));
+        assert!(find_all_solutions(&program, -1).is_empty());
+    }
+
+    #[test]
+    fn test_search() {
+        let program = parse(EXAMPLE1).unwrap();
+        let (noun, verb) = search(&program, 100, false).unwrap();
+        assert_eq!(run_with_inputs(&program, noun, verb, false), 100);
+        assert_eq!(search(&program, -1, false), None);
+    }
 }
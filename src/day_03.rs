@@ -1,10 +1,13 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::num::ParseIntError;
-use std::ops::{Add, AddAssign};
+use std::ops::AddAssign;
 use std::str::FromStr;
 
 use thiserror::Error;
 
+use crate::geom::Position;
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
 enum ParseError {
     #[error("Syntax error")]
@@ -57,28 +60,23 @@ impl FromStr for Step {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Wires {
-    first: Vec<Step>,
-    second: Vec<Step>,
+    paths: Vec<Vec<Step>>,
 }
 
 impl FromStr for Wires {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut lines = s.lines();
-        let first = lines
-            .next()
-            .ok_or(ParseError::SyntaxError)?
-            .split(',')
-            .map(str::parse)
-            .collect::<Result<_, _>>()?;
-        let second = lines
-            .next()
-            .ok_or(ParseError::SyntaxError)?
-            .split(',')
-            .map(str::parse)
-            .collect::<Result<_, _>>()?;
-        Ok(Self { first, second })
+        let paths = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.split(',').map(str::parse).collect())
+            .collect::<Result<Vec<_>, _>>()?;
+        if paths.len() < 2 {
+            return Err(ParseError::SyntaxError);
+        }
+        Ok(Self { paths })
     }
 }
 
@@ -87,99 +85,139 @@ fn parse(input: &str) -> Result<Wires, ParseError> {
     input.parse()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-struct Position {
-    x: i64,
-    y: i64,
+impl AddAssign<Step> for Position {
+    fn add_assign(&mut self, rhs: Step) {
+        let count = i64::from(rhs.count);
+        match rhs.direction {
+            Direction::Up => self.y -= count,
+            Direction::Right => self.x += count,
+            Direction::Down => self.y += count,
+            Direction::Left => self.x -= count,
+        }
+    }
 }
 
-impl Position {
-    const fn dist(self) -> u64 {
-        self.x.unsigned_abs() + self.y.unsigned_abs()
-    }
+#[aoc(day3, part1)]
+fn part_1(wires: &Wires) -> Option<u64> {
+    closest_intersection(wires)
 }
 
-impl AddAssign<Direction> for Position {
-    fn add_assign(&mut self, rhs: Direction) {
-        match rhs {
-            Direction::Up => self.y -= 1,
-            Direction::Right => self.x += 1,
-            Direction::Down => self.y += 1,
-            Direction::Left => self.x -= 1,
-        }
-    }
+#[aoc(day3, part2)]
+fn part_2(wires: &Wires) -> Option<u64> {
+    shortest_signal_delay(wires)
 }
 
-impl Add<Direction> for Position {
-    type Output = Self;
+pub struct Day03;
 
-    fn add(mut self, rhs: Direction) -> Self::Output {
-        self += rhs;
-        self
+impl Solver for Day03 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
     }
-}
 
-#[aoc(day3, part1)]
-fn part_1(wires: &Wires) -> u64 {
-    let mut visited = HashSet::new();
-    let mut closest_dist = u64::MAX;
-    for pos in WireStepper::new(&wires.first) {
-        visited.insert(pos);
-    }
-    for pos in WireStepper::new(&wires.second) {
-        if visited.contains(&pos) {
-            closest_dist = closest_dist.min(pos.dist());
-        }
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
     }
-    closest_dist
 }
 
-#[aoc(day3, part2)]
-fn part_2(wires: &Wires) -> u64 {
-    let mut visited = HashMap::new();
-    for (pos, time1) in WireStepper::new(&wires.first).zip(1..) {
-        visited.entry(pos).or_insert(time1);
-    }
-    let mut minimum_steps = u64::MAX;
-    for (pos, time2) in WireStepper::new(&wires.second).zip(1..) {
-        if let Some(&time1) = visited.get(&pos) {
-            minimum_steps = minimum_steps.min(time2 + time1);
-        }
-    }
-    minimum_steps
+/// Manhattan distance to the intersection closest to the origin, if any wires cross at all.
+fn closest_intersection(wires: &Wires) -> Option<u64> {
+    intersections(wires)
+        .into_iter()
+        .map(Position::magnitude)
+        .min()
 }
 
-struct WireStepper<'a> {
-    steps: &'a [Step],
-    index: usize,
-    stepped: u16,
-    position: Position,
+/// Fewest combined steps two wires take to reach a shared point, if any wires cross at all.
+///
+/// Where three or more wires meet, the delay is that of the closest pair among them; the steps
+/// of any further wires are not added on.
+fn shortest_signal_delay(wires: &Wires) -> Option<u64> {
+    crossings(wires).into_iter().map(|(_, delay)| delay).min()
 }
 
-impl<'a> WireStepper<'a> {
-    fn new(steps: &'a [Step]) -> Self {
-        Self {
-            steps,
-            index: 0,
-            stepped: 0,
-            position: Position::default(),
+/// Every position touched by at least two distinct wires.
+///
+/// Pairs of wires are visited in input order, and within each pair the positions are listed in
+/// the order the later wire reaches them.
+fn intersections(wires: &Wires) -> Vec<Position> {
+    let mut seen = HashSet::new();
+    crossings(wires)
+        .into_iter()
+        .map(|(pos, _)| pos)
+        .filter(|&pos| seen.insert(pos))
+        .collect()
+}
+
+/// Every point where two distinct wires meet, together with the combined number of steps the
+/// pair took along their segments to get there.
+fn crossings(wires: &Wires) -> Vec<(Position, u64)> {
+    let segmented = wires
+        .paths
+        .iter()
+        .map(|path| segments(path))
+        .collect::<Vec<_>>();
+    let mut result = Vec::new();
+    for (ix, second) in segmented.iter().enumerate() {
+        for first in &segmented[..ix] {
+            result.extend(pair_crossings(first, second));
         }
     }
+    result
 }
 
-impl Iterator for WireStepper<'_> {
-    type Item = Position;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let current = *self.steps.get(self.index)?;
-        self.position += current.direction;
-        self.stepped += 1;
-        if self.stepped >= current.count {
-            self.index += 1;
-            self.stepped = 0;
+/// Every point where a segment of the second wire meets a segment of the first wire, together
+/// with the combined number of steps both wires took along those segments to get there.
+fn pair_crossings(
+    first: &[(Position, Position)],
+    second: &[(Position, Position)],
+) -> Vec<(Position, u64)> {
+    let mut result = Vec::new();
+    let mut delay2 = 0;
+    for &(start2, end2) in second {
+        let mut found = Vec::new();
+        let mut delay1 = 0;
+        for &(start1, end1) in first {
+            for pos in overlap((start1, end1), (start2, end2)) {
+                if pos != Position::default() {
+                    let delay = delay1 + start1.manhattan(pos) + delay2 + start2.manhattan(pos);
+                    found.push((pos, delay));
+                }
+            }
+            delay1 += start1.manhattan(end1);
         }
-        Some(self.position)
+        found.sort_by_key(|&(pos, _)| start2.manhattan(pos));
+        result.extend(found);
+        delay2 += start2.manhattan(end2);
     }
+    result
+}
+
+/// Straight line segments traced by the wire, starting at the origin.
+fn segments(steps: &[Step]) -> Vec<(Position, Position)> {
+    let mut pos = Position::default();
+    steps
+        .iter()
+        .map(|&step| {
+            let start = pos;
+            pos += step;
+            (start, pos)
+        })
+        .collect()
+}
+
+/// Points shared by two axis-aligned segments.
+///
+/// Each segment is its own bounding box, so the shared points are the intersection of the two
+/// boxes: a single point when perpendicular, a run of points when overlapping along one line.
+fn overlap(
+    (start1, end1): (Position, Position),
+    (start2, end2): (Position, Position),
+) -> impl Iterator<Item = Position> {
+    let min_x = start1.x.min(end1.x).max(start2.x.min(end2.x));
+    let max_x = start1.x.max(end1.x).min(start2.x.max(end2.x));
+    let min_y = start1.y.min(end1.y).max(start2.y.min(end2.y));
+    let max_y = start1.y.max(end1.y).min(start2.y.max(end2.y));
+    (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| Position { x, y }))
 }
 
 #[cfg(test)]
@@ -215,28 +253,112 @@ mod tests {
     fn test_parse() {
         let result = parse(EXAMPLE1).unwrap();
         assert_eq!(
-            result.first,
-            [step!(Right 8), step!(Up 5), step!(Left 5), step!(Down 3)]
+            result.paths,
+            [
+                [step!(Right 8), step!(Up 5), step!(Left 5), step!(Down 3)],
+                [step!(Up 7), step!(Right 6), step!(Down 4), step!(Left 4)],
+            ]
         );
+    }
+
+    #[test]
+    fn test_intersections() {
+        let wires = parse(EXAMPLE1).unwrap();
         assert_eq!(
-            result.second,
-            [step!(Up 7), step!(Right 6), step!(Down 4), step!(Left 4)]
+            intersections(&wires),
+            [Position { x: 6, y: -5 }, Position { x: 3, y: -3 }]
         );
     }
 
-    #[test_case(EXAMPLE1 => 6)]
-    #[test_case(EXAMPLE2 => 159)]
-    #[test_case(EXAMPLE3 => 135)]
-    fn test_part_1(input: &str) -> u64 {
+    #[test]
+    fn test_intersections_ignores_self_crossing() {
+        // The first wire crosses itself at (2, 0), and the second at (0, 1). Neither point is
+        // touched by the other wire.
+        let first = "R4,U2,L2,D4";
+        let second = "D3,L3,U2,R5";
+        for (wire, point) in [
+            (first, Position { x: 2, y: 0 }),
+            (second, Position { x: 0, y: 1 }),
+        ] {
+            let steps = wire
+                .split(',')
+                .map(|s| s.parse().unwrap())
+                .collect::<Vec<_>>();
+            let passes = segments(&steps)
+                .into_iter()
+                .filter(|&segment| overlap(segment, (point, point)).next().is_some())
+                .count();
+            assert_eq!(passes, 2, "{wire} should cross itself at {point:?}");
+        }
+        for input in [format!("{first}\n{second}"), format!("{second}\n{first}")] {
+            let wires = parse(&input).unwrap();
+            assert_eq!(intersections(&wires), [Position { x: 2, y: 1 }]);
+        }
+    }
+
+    #[test_case(EXAMPLE1 => Some(6))]
+    #[test_case(EXAMPLE2 => Some(159))]
+    #[test_case(EXAMPLE3 => Some(135))]
+    fn test_part_1(input: &str) -> Option<u64> {
         let wires = parse(input).unwrap();
         part_1(&wires)
     }
 
-    #[test_case(EXAMPLE1 => 30)]
-    #[test_case(EXAMPLE2 => 610)]
-    #[test_case(EXAMPLE3 => 410)]
-    fn test_part_2(input: &str) -> u64 {
+    #[test_case(EXAMPLE1 => Some(30))]
+    #[test_case(EXAMPLE2 => Some(610))]
+    #[test_case(EXAMPLE3 => Some(410))]
+    fn test_part_2(input: &str) -> Option<u64> {
         let wires = parse(input).unwrap();
         part_2(&wires)
     }
+
+    #[test]
+    fn test_long_wires() {
+        // A staircase 60 steps high, met by a wire going up along the axis and then right
+        let first = vec!["R1000,U1000"; 60].join(",");
+        let input = format!("{first}\nU60000,R60000");
+        let wires = parse(&input).unwrap();
+        assert_eq!(intersections(&wires), [Position { x: 60_000, y: -60_000 }]);
+        assert_eq!(part_1(&wires), Some(120_000));
+        assert_eq!(part_2(&wires), Some(240_000));
+    }
+
+    #[test]
+    fn test_overlapping_segments() {
+        let wires = parse("R10\nU1,R3,D1,R4").unwrap();
+        assert_eq!(
+            intersections(&wires),
+            [
+                Position { x: 3, y: 0 },
+                Position { x: 4, y: 0 },
+                Position { x: 5, y: 0 },
+                Position { x: 6, y: 0 },
+                Position { x: 7, y: 0 },
+            ]
+        );
+        assert_eq!(part_2(&wires), Some(3 + 5));
+    }
+
+    #[test]
+    fn test_three_wires() {
+        let wires = parse("R10\nU1,R3,D2\nD1,R6,U2").unwrap();
+        assert_eq!(
+            intersections(&wires),
+            [
+                Position { x: 3, y: 0 },
+                Position { x: 6, y: 0 },
+                Position { x: 3, y: 1 },
+            ]
+        );
+        assert_eq!(closest_intersection(&wires), Some(3));
+        assert_eq!(shortest_signal_delay(&wires), Some(3 + 5));
+    }
+
+    #[test_case("R5,U5\nL5,D5"; "never cross")]
+    #[test_case("R5\nL5"; "only share the origin")]
+    fn test_no_intersection(input: &str) {
+        let wires = parse(input).unwrap();
+        assert_eq!(closest_intersection(&wires), None);
+        assert_eq!(shortest_signal_delay(&wires), None);
+    }
 }
@@ -1,50 +1,52 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use thiserror::Error;
 
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
-enum ParseError {
+pub enum ParseError {
     #[error("Syntax error")]
     SyntaxError,
 }
 
-type Password = [u8; 6];
+pub type Password<const N: usize> = [u8; N];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct PasswordRange {
-    lower: Password,
-    upper: Password,
+pub struct PasswordRange<const N: usize> {
+    lower: Password<N>,
+    upper: Password<N>,
 }
 
-impl FromStr for PasswordRange {
+impl<const N: usize> FromStr for PasswordRange<N> {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let bytes = s.as_bytes();
-        if bytes.len() != 13 || bytes[6] != b'-' {
+        if bytes.len() != 2 * N + 1 || bytes[N] != b'-' {
             return Err(ParseError::SyntaxError);
         }
         Ok(Self {
-            lower: s.as_bytes()[..6].try_into().unwrap(),
-            upper: s.as_bytes()[7..].try_into().unwrap(),
+            lower: bytes[..N].try_into().unwrap(),
+            upper: bytes[N + 1..].try_into().unwrap(),
         })
     }
 }
 
 #[aoc_generator(day4)]
-fn parse(input: &str) -> Result<PasswordRange, ParseError> {
+fn parse(input: &str) -> Result<PasswordRange<6>, ParseError> {
     input.parse()
 }
 
 #[aoc(day4, part1)]
-fn part_1(range: &PasswordRange) -> usize {
+fn part_1(range: &PasswordRange<6>) -> usize {
     PasswordEnumerator::new(range)
         .filter(is_valid_part_1)
         .count()
 }
 
-#[expect(clippy::trivially_copy_pass_by_ref, reason = "filter")]
-fn is_valid_part_1(password: &Password) -> bool {
+fn is_valid_part_1<const N: usize>(password: &Password<N>) -> bool {
     let mut counts = [0_u8; 10];
     let mut prev = 0;
     for &ch in password {
@@ -57,14 +59,30 @@ fn is_valid_part_1(password: &Password) -> bool {
 }
 
 #[aoc(day4, part2)]
-fn part_2(range: &PasswordRange) -> usize {
-    PasswordEnumerator::new(range)
-        .filter(is_valid_part_2)
-        .count()
+fn part_2(range: &PasswordRange<6>) -> usize {
+    valid_part_2_iter(range).count()
+}
+
+pub struct Day04;
+
+impl Solver for Day04 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
+}
+
+/// Every password in the range that satisfies the part 2 rules, in increasing order.
+pub fn valid_part_2_iter<const N: usize>(
+    range: &PasswordRange<N>,
+) -> impl Iterator<Item = Password<N>> + '_ {
+    PasswordEnumerator::new(range).filter(is_valid_part_2)
 }
 
-#[expect(clippy::trivially_copy_pass_by_ref, reason = "filter")]
-fn is_valid_part_2(password: &Password) -> bool {
+fn is_valid_part_2<const N: usize>(password: &Password<N>) -> bool {
     let mut counts = [0_u8; 10];
     let mut prev = 0_u8;
     for &ch in password {
@@ -76,14 +94,105 @@ fn is_valid_part_2(password: &Password) -> bool {
     counts.into_iter().any(|c| c == 2)
 }
 
+/// Counts the valid passwords in the range without enumerating them.
+///
+/// Walks the digits of each bound, and for every smaller digit that could be placed instead,
+/// counts the non-decreasing completions of the remaining digits. Those counts only depend on
+/// the number of remaining digits, the previous digit and the length of the current run, so
+/// they are memoized.
+#[allow(unused, reason = "tests")]
+fn count_valid<const N: usize>(range: &PasswordRange<N>, require_exact_pair: bool) -> usize {
+    let is_valid = if require_exact_pair {
+        is_valid_part_2
+    } else {
+        is_valid_part_1
+    };
+    let mut counter = RunCounter::new(require_exact_pair);
+    let below_upper = counter.count_at_most(range.upper);
+    let below_lower = counter.count_at_most(range.lower);
+    (below_upper + usize::from(is_valid(&range.lower))).saturating_sub(below_lower)
+}
+
+struct RunCounter {
+    require_exact_pair: bool,
+    memo: HashMap<(usize, u8, u8, bool), usize>,
+}
+
+impl RunCounter {
+    fn new(require_exact_pair: bool) -> Self {
+        Self {
+            require_exact_pair,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Whether a finished run of this length satisfies the pair requirement.
+    const fn qualifies(&self, run: u8) -> bool {
+        if self.require_exact_pair {
+            run == 2
+        } else {
+            run >= 2
+        }
+    }
+
+    /// State after appending `digit`. Run lengths above three are all equivalent.
+    const fn append(&self, prev: u8, run: u8, ok: bool, digit: u8) -> (u8, bool) {
+        if digit == prev {
+            (if run < 3 { run + 1 } else { 3 }, ok)
+        } else {
+            (1, ok || self.qualifies(run))
+        }
+    }
+
+    /// Number of non-decreasing valid passwords less than or equal to `bound`.
+    fn count_at_most<const N: usize>(&mut self, bound: Password<N>) -> usize {
+        let mut total = 0;
+        let (mut prev, mut run, mut ok) = (0, 0, false);
+        for (ix, &ch) in bound.iter().enumerate() {
+            let limit = ch - b'0';
+            for digit in prev..limit {
+                let (next_run, next_ok) = self.append(prev, run, ok, digit);
+                total += self.completions(bound.len() - ix - 1, digit, next_run, next_ok);
+            }
+            if limit < prev {
+                return total;
+            }
+            (run, ok) = self.append(prev, run, ok, limit);
+            prev = limit;
+        }
+        total + usize::from(ok || self.qualifies(run))
+    }
+
+    /// Number of ways to fill the remaining digits so that the password becomes valid.
+    fn completions(&mut self, remaining: usize, prev: u8, run: u8, ok: bool) -> usize {
+        if remaining == 0 {
+            return usize::from(ok || self.qualifies(run));
+        }
+        if let Some(&count) = self.memo.get(&(remaining, prev, run, ok)) {
+            return count;
+        }
+        let mut count = 0;
+        for digit in prev..=9 {
+            let (next_run, next_ok) = self.append(prev, run, ok, digit);
+            count += self.completions(remaining - 1, digit, next_run, next_ok);
+        }
+        self.memo.insert((remaining, prev, run, ok), count);
+        count
+    }
+}
+
+/// Every password in a range whose digits never decrease, in increasing order.
 #[derive(Debug, Clone)]
-struct PasswordEnumerator<'a> {
-    range: &'a PasswordRange,
-    next: Password,
+pub struct PasswordEnumerator<'a, const N: usize> {
+    range: &'a PasswordRange<N>,
+    next: Password<N>,
 }
 
-impl<'a> PasswordEnumerator<'a> {
-    fn new(range: &'a PasswordRange) -> Self {
+impl<'a, const N: usize> PasswordEnumerator<'a, N> {
+    /// Starts at the first password with non-decreasing digits that is not below the lower bound
+    /// of `range`.
+    #[must_use]
+    pub fn new(range: &'a PasswordRange<N>) -> Self {
         let mut next = range.lower;
         // Start at first increasing sequence
         let mut max = b'0';
@@ -95,8 +204,8 @@ impl<'a> PasswordEnumerator<'a> {
     }
 }
 
-impl Iterator for PasswordEnumerator<'_> {
-    type Item = Password;
+impl<const N: usize> Iterator for PasswordEnumerator<'_, N> {
+    type Item = Password<N>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.next > self.range.upper {
@@ -129,7 +238,7 @@ mod tests {
     #[test_case(b"223450" => false)]
     #[test_case(b"123789" => false)]
     #[allow(clippy::trivially_copy_pass_by_ref, reason = "byte literals")]
-    fn test_valid_part_1(password: &Password) -> bool {
+    fn test_valid_part_1(password: &Password<6>) -> bool {
         is_valid_part_1(password)
     }
 
@@ -137,7 +246,47 @@ mod tests {
     #[test_case(b"123444" => false)]
     #[test_case(b"111122" => true)]
     #[allow(clippy::trivially_copy_pass_by_ref, reason = "byte literals")]
-    fn test_valid_part_2(password: &Password) -> bool {
+    fn test_valid_part_2(password: &Password<6>) -> bool {
         is_valid_part_2(password)
     }
+
+    #[test_case("134564-585159")]
+    #[test_case("100000-899999")]
+    #[test_case("111111-111122")]
+    #[test_case("123444-123444")]
+    #[test_case("987654-998999")]
+    fn test_count_valid(input: &str) {
+        let range = parse(input).unwrap();
+        assert_eq!(count_valid(&range, false), part_1(&range));
+        assert_eq!(count_valid(&range, true), part_2(&range));
+    }
+
+    #[test]
+    fn test_other_lengths() {
+        let range = "1234-5678".parse::<PasswordRange<4>>().unwrap();
+        let part_1 = PasswordEnumerator::new(&range).filter(is_valid_part_1);
+        assert_eq!(part_1.count(), 270);
+        let part_2 = PasswordEnumerator::new(&range).filter(is_valid_part_2);
+        assert_eq!(part_2.count(), 218);
+
+        let range = "11111111-11111199".parse::<PasswordRange<8>>().unwrap();
+        let part_1 = PasswordEnumerator::new(&range).filter(is_valid_part_1);
+        assert_eq!(part_1.count(), 45);
+        let part_2 = PasswordEnumerator::new(&range).filter(is_valid_part_2);
+        assert_eq!(part_2.count(), 8);
+    }
+
+    #[test]
+    fn test_valid_part_2_iter() {
+        let range = parse("134564-585159").unwrap();
+        let valid = valid_part_2_iter(&range).collect::<Vec<_>>();
+        assert_eq!(valid.len(), 1306);
+        assert_eq!(valid.first(), Some(b"134566"));
+        assert_eq!(valid.last(), Some(b"578899"));
+    }
+
+    #[test]
+    fn test_parse_wrong_length() {
+        assert!("123456-654321".parse::<PasswordRange<4>>().is_err());
+    }
 }
@@ -1,6 +1,7 @@
 use std::num::ParseIntError;
 
-use crate::machine::{parse_program, Machine, Value};
+use crate::machine::{parse_program, run_collecting, Machine, Value};
+use crate::solver::{Answer, Solver};
 
 #[aoc_generator(day5)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
@@ -9,18 +10,45 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day5, part1)]
 fn part_1(program: &[Value]) -> Value {
-    let mut machine = Machine::new(program);
-    machine.inputs.push_back(1);
-    machine.run_until_stopped().unwrap();
-    machine.outputs.pop_back().unwrap()
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> Value {
+    let machine = Machine::new(program).with_log(trace);
+    run_collecting(machine, &[1]).unwrap().pop().unwrap()
 }
 
 #[aoc(day5, part2)]
 fn part_2(program: &[Value]) -> Value {
-    let mut machine = Machine::new(program);
-    machine.inputs.push_back(5);
-    machine.run_until_output().unwrap();
-    machine.outputs.pop_back().unwrap()
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> Value {
+    let machine = Machine::new(program).with_log(trace);
+    run_collecting(machine, &[5]).unwrap().pop().unwrap()
+}
+
+#[derive(Default)]
+pub struct Day05 {
+    trace: bool,
+}
+
+impl Solver for Day05 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
 }
 
 #[cfg(test)]
@@ -3,10 +3,16 @@ use std::str::FromStr;
 
 use thiserror::Error;
 
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
 enum ParseError {
     #[error("Syntax error")]
     SyntaxError,
+    #[error("Orbits form a cycle")]
+    Cycle,
+    #[error("Some objects do not orbit COM, directly or indirectly")]
+    Disconnected,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -33,6 +39,45 @@ impl Object {
 #[derive(Debug, Clone)]
 struct Map {
     direct_orbits: Vec<Object>, // [Com=0] = Com
+    #[allow(unused, reason = "tests")]
+    names: HashMap<String, Object>,
+}
+
+impl Map {
+    /// Objects the given object orbits, directly or indirectly, ending with COM.
+    fn ancestors(&self, object: Object) -> Vec<Object> {
+        let mut result = Vec::new();
+        let mut node = object;
+        while node != Object::Com {
+            node = self.direct_orbits[node.index()];
+            result.push(node);
+        }
+        result
+    }
+
+    /// Index of the named object, as used by `direct_orbits`.
+    #[allow(unused, reason = "tests")]
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.get(name).map(|object| object.index())
+    }
+
+    /// Number of objects that directly or indirectly orbit the given object.
+    #[allow(unused, reason = "tests")]
+    fn count_orbiting(&self, name_index: usize) -> usize {
+        let mut children = vec![vec![]; self.direct_orbits.len()];
+        for (ix, &parent) in self.direct_orbits.iter().enumerate() {
+            if parent != Object::Unknown && ix != Object::Com.index() {
+                children[parent.index()].push(ix);
+            }
+        }
+        let mut count = 0;
+        let mut pending = children[name_index].clone();
+        while let Some(ix) = pending.pop() {
+            count += 1;
+            pending.extend(&children[ix]);
+        }
+        count
+    }
 }
 
 impl FromStr for Map {
@@ -58,10 +103,56 @@ impl FromStr for Map {
             let right = *names.get(rhs).unwrap();
             direct_orbits[right.index()] = left;
         }
-        Ok(Self { direct_orbits })
+        if direct_orbits[Object::Com.index()] != Object::Com {
+            return Err(ParseError::Cycle);
+        }
+        check_acyclic(&direct_orbits)?;
+        let names = names
+            .into_iter()
+            .map(|(name, object)| (name.to_string(), object))
+            .collect();
+        Ok(Self {
+            direct_orbits,
+            names,
+        })
     }
 }
 
+/// Follows the orbits from every object, and fails if any walk comes back to an object it has
+/// already passed, or ends at an object other than COM that orbits nothing. YOU and SAN are
+/// always listed, so they are only allowed to orbit nothing when nothing orbits them.
+fn check_acyclic(direct_orbits: &[Object]) -> Result<(), ParseError> {
+    let mut done = vec![false; direct_orbits.len()];
+    let mut on_path = vec![false; direct_orbits.len()];
+    done[Object::Com.index()] = true;
+    for start in 0..direct_orbits.len() {
+        if direct_orbits[start] == Object::Unknown
+            && (start == Object::You.index() || start == Object::San.index())
+        {
+            // Not in the map, unless another object orbits it
+            continue;
+        }
+        let mut path = Vec::new();
+        let mut node = start;
+        while !done[node] {
+            if on_path[node] {
+                return Err(ParseError::Cycle);
+            }
+            on_path[node] = true;
+            path.push(node);
+            match direct_orbits[node] {
+                Object::Unknown => return Err(ParseError::Disconnected),
+                parent => node = parent.index(),
+            }
+        }
+        for ix in path {
+            on_path[ix] = false;
+            done[ix] = true;
+        }
+    }
+    Ok(())
+}
+
 #[aoc_generator(day6)]
 fn parse(input: &str) -> Result<Map, ParseError> {
     input.parse()
@@ -95,41 +186,40 @@ fn part_1(map: &Map) -> usize {
 
 #[aoc(day6, part2)]
 fn part_2(map: &Map) -> usize {
-    let mut you_depth = 0;
-    let mut you_node = Object::You;
-    while you_node != Object::Com {
-        you_node = map.direct_orbits[you_node.index()];
-        you_depth += 1;
-    }
-    let mut san_depth = 0;
-    let mut san_node = Object::San;
-    while san_node != Object::Com {
-        san_node = map.direct_orbits[san_node.index()];
-        san_depth += 1;
-    }
-    you_node = Object::You;
-    san_node = Object::San;
-    if san_depth < you_depth {
-        for _ in san_depth..you_depth {
-            you_node = map.direct_orbits[you_node.index()];
-        }
-    } else if san_depth > you_depth {
-        for _ in you_depth..san_depth {
-            san_node = map.direct_orbits[san_node.index()];
-        }
+    transfer_path(map).len() - 1
+}
+
+pub struct Day06;
+
+impl Solver for Day06 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
     }
-    let mut common_depth = san_depth.min(you_depth);
-    while you_node != san_node {
-        you_node = map.direct_orbits[you_node.index()];
-        san_node = map.direct_orbits[san_node.index()];
-        common_depth -= 1;
+}
+
+/// Objects visited when transferring from the object YOU orbit to the object SAN orbits, by way
+/// of their closest common ancestor. Both ends are included.
+fn transfer_path(map: &Map) -> Vec<Object> {
+    let mut you_path = map.ancestors(Object::You);
+    let mut san_path = map.ancestors(Object::San);
+    let mut common = None;
+    while you_path.last().is_some() && you_path.last() == san_path.last() {
+        common = you_path.pop();
+        san_path.pop();
     }
-    you_depth + san_depth - common_depth * 2 - 2
+    you_path.push(common.expect("YOU and SAN do not share an ancestor"));
+    you_path.extend(san_path.into_iter().rev());
+    you_path
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
 
     const EXAMPLE1: &str = "\
         COM)B\n\
@@ -185,6 +275,24 @@ mod tests {
         );
     }
 
+    #[test_case("COM)B\nB)C\nD)E\nE)D"; "two-node cycle")]
+    #[test_case("COM)B\nB)COM"; "cycle through COM")]
+    fn test_parse_cycle(input: &str) {
+        let result = parse(input);
+        assert!(matches!(result, Err(ParseError::Cycle)), "{result:?}");
+    }
+
+    #[test_case("COM)B\nX)Y"; "second root")]
+    #[test_case("COM)B\nYOU)X"; "orbiting YOU")]
+    #[test_case("COM)B\nX)SAN"; "SAN orbiting a second root")]
+    fn test_parse_disconnected(input: &str) {
+        let result = parse(input);
+        assert!(
+            matches!(result, Err(ParseError::Disconnected)),
+            "{result:?}"
+        );
+    }
+
     #[test]
     fn test_part_1() {
         let map = parse(EXAMPLE1).unwrap();
@@ -192,10 +300,36 @@ mod tests {
         assert_eq!(result, 42);
     }
 
+    #[test_case("COM" => 11)]
+    #[test_case("D" => 6)]
+    #[test_case("K" => 1)]
+    #[test_case("L" => 0)]
+    fn test_count_orbiting(name: &str) -> usize {
+        let map = parse(EXAMPLE1).unwrap();
+        map.count_orbiting(map.index_of(name).unwrap())
+    }
+
     #[test]
     fn test_part_2() {
         let map = parse(EXAMPLE2).unwrap();
         let result = part_2(&map);
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn test_transfer_path() {
+        let map = parse(EXAMPLE2).unwrap();
+        let result = transfer_path(&map);
+        assert_eq!(
+            result,
+            [
+                Object::Other(12), // K
+                Object::Other(11), // J
+                Object::Other(6),  // E
+                Object::Other(5),  // D
+                Object::Other(10), // I
+            ]
+        );
+        assert_eq!(result.len() - 1, part_2(&map));
+    }
 }
@@ -1,8 +1,7 @@
 use std::num::ParseIntError;
 
-use thiserror::Error;
-
-use crate::machine::{Machine, MachineError, Value, parse_program};
+use crate::machine::{Machine, MachineError, Scheduler, Value, parse_program};
+use crate::solver::{Answer, Solver};
 
 #[aoc_generator(day7)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
@@ -11,81 +10,99 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day7, part1)]
 fn part_1(program: &[Value]) -> Value {
-    let mut amplifier = Amplifiers::new(program);
-    let mut max_signal = Value::MIN;
-    permute(&mut [0, 1, 2, 3, 4], 0, &mut |phase_settings| {
-        amplifier.reset(*phase_settings);
-        if let Ok(signal) = amplifier.get_chain_output(0) {
-            max_signal = max_signal.max(signal);
-        }
-    });
-    max_signal
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> Value {
+    best_phase_setting(program, [0, 1, 2, 3, 4], trace).0
 }
 
 #[aoc(day7, part2)]
 fn part_2(program: &[Value]) -> Value {
-    let mut amplifiers = Amplifiers::new(program);
-    let mut max_signal = Value::MIN;
-    permute(&mut [5, 6, 7, 8, 9], 0, &mut |&phase_settings| {
-        amplifiers.reset(phase_settings);
-        let mut signal = 0;
-        while let Ok(new_signal) = amplifiers.get_chain_output(signal) {
-            signal = new_signal;
-        }
-        max_signal = max_signal.max(signal);
-    });
-    max_signal
+    part_2_traced(program, false)
 }
 
-fn permute<const N: usize, T>(items: &mut [T; N], index: usize, report: &mut impl FnMut(&[T; N])) {
-    if index == N {
-        report(items);
-    } else {
-        for next in index..N {
-            items.swap(index, next);
-            permute(items, index + 1, report);
-            items.swap(index, next);
-        }
-    }
+fn part_2_traced(program: &[Value], trace: bool) -> Value {
+    best_phase_setting(program, [5, 6, 7, 8, 9], trace).0
 }
 
-#[derive(Debug, Error)]
-enum RuntimeError {
-    #[error(transparent)]
-    MachineError(#[from] MachineError),
-    #[error("No output produced")]
-    OutputEmpty,
+#[derive(Default)]
+pub struct Day07 {
+    trace: bool,
 }
 
-struct Amplifiers<'a> {
-    program: &'a [Value],
-    machines: [Machine; 5],
-}
+impl Solver for Day07 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
 
-impl<'a> Amplifiers<'a> {
-    fn new(program: &'a [Value]) -> Self {
-        Self {
-            program,
-            machines: [(); 5].map(|()| Machine::new(program)),
-        }
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
     }
 
-    fn reset(&mut self, phase_settings: [Value; 5]) {
-        for (machine, phase) in self.machines.iter_mut().zip(phase_settings) {
-            machine.reset(self.program);
-            machine.inputs.push_back(phase);
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
+/// Highest thruster signal over all orderings of `phases`, and the ordering that produced it.
+///
+/// The signal is fed back through the chain until the amplifiers halt, so a program that halts
+/// after a single pass behaves as a plain chain.
+fn best_phase_setting<const N: usize>(
+    program: &[Value],
+    mut phases: [Value; N],
+    trace: bool,
+) -> (Value, [Value; N]) {
+    let mut amplifiers = Scheduler::new(vec![Machine::new(program).with_log(trace); N]);
+    let mut best = (Value::MIN, phases);
+    permute(&mut phases, 0, &mut |&phase_settings| {
+        if let Ok(Some(signal)) = thruster_signal(&mut amplifiers, program, phase_settings)
+            && signal > best.0
+        {
+            best = (signal, phase_settings);
         }
+    });
+    best
+}
+
+/// Last signal sent to the thrusters by a chain of amplifiers with the given phase settings, where
+/// the output of the last amplifier is fed back into the first. `None` if it never sent one.
+///
+/// The machines in `amplifiers` are reset to `program` before the run, so they can be reused.
+fn thruster_signal<const N: usize>(
+    amplifiers: &mut Scheduler,
+    program: &[Value],
+    phase_settings: [Value; N],
+) -> Result<Option<Value>, MachineError> {
+    for (machine, phase) in amplifiers.machines.iter_mut().zip(phase_settings) {
+        machine.reset(program);
+        machine.inputs.push_back(phase);
     }
+    amplifiers.machines[0].inputs.push_back(0);
+    let mut signal = None;
+    amplifiers.run(&mut |ix, value| {
+        if ix == N - 1 {
+            signal = Some(value);
+        }
+        Some((ix + 1) % N)
+    })?;
+    Ok(signal)
+}
 
-    fn get_chain_output(&mut self, first_input: Value) -> Result<Value, RuntimeError> {
-        let mut signal = first_input;
-        for machine in &mut self.machines {
-            machine.inputs.push_back(signal);
-            signal = machine
-                .run_until_output()?
-                .ok_or(RuntimeError::OutputEmpty)?;
+fn permute<const N: usize, T>(items: &mut [T; N], index: usize, report: &mut impl FnMut(&[T; N])) {
+    if index == N {
+        report(items);
+    } else {
+        for next in index..N {
+            items.swap(index, next);
+            permute(items, index + 1, report);
+            items.swap(index, next);
         }
-        Ok(signal)
     }
 }
 
@@ -124,6 +141,25 @@ mod tests {
         part_1(&program)
     }
 
+    #[test]
+    fn test_three_amplifiers() {
+        let program = parse(EXAMPLE1).unwrap();
+        assert_eq!(
+            best_phase_setting(&program, [0, 1, 2], false),
+            (210, [2, 1, 0])
+        );
+    }
+
+    #[test_case(EXAMPLE1, [0, 1, 2, 3, 4] => (43_210, [4, 3, 2, 1, 0]))]
+    #[test_case(EXAMPLE2, [0, 1, 2, 3, 4] => (54_321, [0, 1, 2, 3, 4]))]
+    #[test_case(EXAMPLE3, [0, 1, 2, 3, 4] => (65_210, [1, 0, 4, 3, 2]))]
+    #[test_case(EXAMPLE4, [5, 6, 7, 8, 9] => (139_629_729, [9, 8, 7, 6, 5]))]
+    #[test_case(EXAMPLE5, [5, 6, 7, 8, 9] => (18_216, [9, 7, 8, 5, 6]))]
+    fn test_best_phase_setting(input: &str, phases: [Value; 5]) -> (Value, [Value; 5]) {
+        let program = parse(input).unwrap();
+        best_phase_setting(&program, phases, false)
+    }
+
     #[test_case(EXAMPLE4 => 139_629_729)]
     #[test_case(EXAMPLE5 => 18_216)]
     fn test_part_2(input: &str) -> Value {
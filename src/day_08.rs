@@ -1,16 +1,113 @@
+use std::cmp::Reverse;
+
+use thiserror::Error;
+
+use crate::render;
+use crate::solver::{Answer, Solver};
+
+const WIDTH: usize = 25;
+const HEIGHT: usize = 6;
+const TRANSPARENT: u8 = b'2';
+
+#[derive(Debug, Error, PartialEq, Eq)]
+enum DecodeError {
+    #[error("{len} pixels do not divide into layers of {width}x{height}")]
+    RaggedImage {
+        len: usize,
+        width: usize,
+        height: usize,
+    },
+    #[error("Layers of {width}x{height} have no pixels")]
+    EmptyLayer { width: usize, height: usize },
+    #[error("The image has no layers")]
+    NoLayers,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Image<'a> {
+    width: usize,
+    height: usize,
+    layers: Vec<&'a [u8]>,
+}
+
+fn decode(input: &[u8], width: usize, height: usize) -> Result<Image<'_>, DecodeError> {
+    if width * height == 0 {
+        return Err(DecodeError::EmptyLayer { width, height });
+    }
+    if !input.len().is_multiple_of(width * height) {
+        return Err(DecodeError::RaggedImage {
+            len: input.len(),
+            width,
+            height,
+        });
+    }
+    Ok(Image {
+        width,
+        height,
+        layers: input.chunks_exact(width * height).collect(),
+    })
+}
+
+/// Decodes the image without knowing its dimensions.
+///
+/// Inputs that divide into 25×6 layers are assumed to be puzzle inputs. Anything else is taken as
+/// a single layer, with the most square dimensions that fit; the number of layers can not be
+/// told apart from the height of the image. `None` if there are no pixels to guess from.
+#[allow(unused, reason = "tests")]
+fn decode_square_guess(input: &[u8]) -> Option<Image<'_>> {
+    if input.is_empty() {
+        return None;
+    }
+    let (width, height) = if input.len().is_multiple_of(WIDTH * HEIGHT) {
+        (WIDTH, HEIGHT)
+    } else {
+        let height = (1..=input.len())
+            .take_while(|h| h * h <= input.len())
+            .filter(|&h| input.len().is_multiple_of(h))
+            .last()
+            .unwrap_or(1);
+        (input.len() / height, height)
+    };
+    decode(input, width, height).ok()
+}
+
 #[aoc(day8, part1)]
-fn part_1(input: &[u8]) -> usize {
-    const LAYER_SIZE: usize = 25 * 6;
-    let [_, one, two] = input
-        .chunks_exact(LAYER_SIZE)
-        .map(get_pixel_count)
+fn part_1(input: &[u8]) -> Result<usize, DecodeError> {
+    let image = decode(input, WIDTH, HEIGHT)?;
+    let counts = image
+        .layers
+        .iter()
+        .map(|layer| get_pixel_count(layer))
         .min()
-        .unwrap();
-    one * two
+        .ok_or(DecodeError::NoLayers)?;
+    Ok(counts[1] * counts[2])
 }
 
-fn get_pixel_count(layer: &[u8]) -> [usize; 3] {
-    let mut count = [0; 3];
+/// Number of layers in the image.
+#[allow(unused, reason = "tests")]
+fn layer_count(input: &[u8], width: usize, height: usize) -> Result<usize, DecodeError> {
+    Ok(decode(input, width, height)?.layers.len())
+}
+
+/// Index of the layer with the most `0` pixels, or the first of them on a tie. `None` if the image
+/// has no layers.
+#[allow(unused, reason = "tests")]
+fn darkest_layer_index(
+    input: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<Option<usize>, DecodeError> {
+    let image = decode(input, width, height)?;
+    Ok(image
+        .layers
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, layer)| Reverse(get_pixel_count(layer)[0]))
+        .map(|(ix, _)| ix))
+}
+
+fn get_pixel_count(layer: &[u8]) -> [usize; 10] {
+    let mut count = [0; 10];
     for &digit in layer {
         count[(digit - b'0') as usize] += 1;
     }
@@ -18,44 +115,55 @@ fn get_pixel_count(layer: &[u8]) -> [usize; 3] {
 }
 
 #[aoc(day8, part2)]
-fn part_2(input: &[u8]) -> String {
-    const WIDTH: usize = 25;
-    const HEIGHT: usize = 6;
-    let image = flatten_layers(input, WIDTH, HEIGHT);
-    render_image(&image, WIDTH, HEIGHT)
+fn part_2(input: &[u8]) -> Result<String, DecodeError> {
+    let image = decode(input, WIDTH, HEIGHT)?;
+    let flattened = flatten_layers(&image, TRANSPARENT);
+    Ok(render_image(&flattened, image.width))
+}
+
+pub struct Day08;
+
+impl Solver for Day08 {
+    fn part1(&self, input: &str) -> String {
+        part_1(input.as_bytes()).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        part_2(input.as_bytes()).into_answer()
+    }
 }
 
-fn flatten_layers(input: &[u8], width: usize, height: usize) -> Vec<u8> {
-    let mut image = vec![b'2'; width * height];
-    for layer in input.chunks_exact(width * height) {
+/// Stacks the layers, with the first layer on top. A pixel stays `transparent` only if it is
+/// transparent in every layer.
+fn flatten_layers(image: &Image, transparent: u8) -> Vec<u8> {
+    let width = image.width;
+    let mut flattened = vec![transparent; width * image.height];
+    for layer in &image.layers {
         for (r, row) in layer.chunks_exact(width).enumerate() {
             for (c, &layer_pixel) in row.iter().enumerate() {
-                let image_pixel = &mut image[r * width + c];
-                if *image_pixel == b'2' {
+                let image_pixel = &mut flattened[r * width + c];
+                if *image_pixel == transparent {
                     *image_pixel = layer_pixel;
                 }
             }
         }
     }
-    image
+    flattened
 }
 
-fn render_image(image: &[u8], width: usize, height: usize) -> String {
-    let mut rendered = String::with_capacity((width * '█'.len_utf8() + 1) * height / 2);
-    for (row1, row2) in image
-        .chunks_exact(width)
-        .zip(image.chunks_exact(width).skip(1))
-        .step_by(2)
-    {
+/// Renders two rows of pixels per line, with white (`1`) pixels drawn as the matching halves of
+/// block characters.
+fn render_image(image: &[u8], width: usize) -> String {
+    render::monochrome_rows(image, width, |&px| px == b'1')
+}
+
+/// Renders one row of pixels per line, choosing the glyph for each pixel with `palette`.
+#[allow(unused, reason = "tests")]
+fn render_image_with(image: &[u8], width: usize, palette: impl Fn(u8) -> char) -> String {
+    let mut rendered = String::new();
+    for row in image.chunks_exact(width) {
         rendered.push('\n');
-        for (&px1, &px2) in row1.iter().zip(row2) {
-            rendered.push(match (px1, px2) {
-                (b'1', b'1') => '█',
-                (b'1', _) => '▀',
-                (_, b'1') => '▄',
-                _ => ' ',
-            });
-        }
+        rendered.extend(row.iter().map(|&px| palette(px)));
     }
     rendered
 }
@@ -63,18 +171,109 @@ fn render_image(image: &[u8], width: usize, height: usize) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn test_decode() {
+        let input = b"123456789012";
+        let result = decode(input, 3, 2).unwrap();
+        assert_eq!(result.layers, [&b"123456"[..], &b"789012"[..]]);
+    }
+
+    #[test]
+    fn test_decode_ragged() {
+        let input = b"12345678901";
+        let result = decode(input, 3, 2);
+        assert_eq!(
+            result,
+            Err(DecodeError::RaggedImage {
+                len: 11,
+                width: 3,
+                height: 2
+            })
+        );
+        assert!(part_1(&[b'0'; 149]).is_err());
+        assert!(part_2(&[b'0'; 151]).is_err());
+    }
+
+    #[test]
+    fn test_part_1_no_layers() {
+        assert_eq!(part_1(b""), Err(DecodeError::NoLayers));
+        assert_eq!(Day08.part1(""), "Error: The image has no layers");
+    }
+
+    #[test_case(b"123456789012", 3, 2 => 2; "two layers")]
+    #[test_case(b"123456789012", 2, 2 => 3; "three layers")]
+    #[test_case(b"", 3, 2 => 0; "empty")]
+    fn test_layer_count(input: &[u8], width: usize, height: usize) -> usize {
+        layer_count(input, width, height).unwrap()
+    }
+
+    #[test_case(b"0222112222120000", 2, 2 => Some(3); "most zeros last")]
+    #[test_case(b"111100110011", 2, 2 => Some(1); "tie")]
+    #[test_case(b"", 2, 2 => None; "no layers")]
+    fn test_darkest_layer_index(input: &[u8], width: usize, height: usize) -> Option<usize> {
+        darkest_layer_index(input, width, height).unwrap()
+    }
+
+    #[test]
+    fn test_layer_statistics_ragged() {
+        assert!(layer_count(b"12345", 3, 2).is_err());
+        assert!(darkest_layer_index(b"12345", 3, 2).is_err());
+    }
+
+    #[test_case(&[b'0'; 300] => (25, 6, 2))]
+    #[test_case(b"0222112222120000" => (4, 4, 1))]
+    #[test_case(b"012012" => (3, 2, 1))]
+    #[test_case(b"01201" => (5, 1, 1))]
+    fn test_decode_square_guess(input: &[u8]) -> (usize, usize, usize) {
+        let image = decode_square_guess(input).unwrap();
+        (image.width, image.height, image.layers.len())
+    }
+
+    #[test]
+    fn test_decode_square_guess_empty() {
+        assert_eq!(decode_square_guess(b""), None);
+    }
+
+    #[test_case(0, 2; "no width")]
+    #[test_case(3, 0; "no height")]
+    fn test_decode_empty_layer(width: usize, height: usize) {
+        assert_eq!(
+            decode(b"", width, height),
+            Err(DecodeError::EmptyLayer { width, height })
+        );
+    }
 
     #[test]
     fn test_flatten_layers() {
         let input = b"0222112222120000";
-        let result = flatten_layers(input, 2, 2);
+        let result = flatten_layers(&decode(input, 2, 2).unwrap(), TRANSPARENT);
+        assert_eq!(result, b"0110");
+    }
+
+    #[test]
+    fn test_flatten_layers_custom_transparent() {
+        let input = b"0333113333130000";
+        let result = flatten_layers(&decode(input, 2, 2).unwrap(), b'3');
         assert_eq!(result, b"0110");
     }
 
     #[test]
     fn test_render_image() {
         let image = b"0110";
-        let result = render_image(image, 2, 2);
+        let result = render_image(image, 2);
         assert_eq!(result, "\n▄▀"); // including linebreak at the start
     }
+
+    #[test]
+    fn test_render_image_palette() {
+        let image = b"0123";
+        let result = render_image_with(image, 2, |px| match px {
+            b'0' => '.',
+            b'1' => '#',
+            _ => '?',
+        });
+        assert_eq!(result, "\n.#\n??");
+    }
 }
@@ -1,6 +1,7 @@
 use std::num::ParseIntError;
 
-use crate::machine::{parse_program, Machine, Value};
+use crate::machine::{parse_program, run_collecting, Machine, Value};
+use crate::solver::{Answer, Solver};
 
 #[aoc_generator(day9)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
@@ -9,18 +10,45 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day9, part1)]
 fn part_1(program: &[Value]) -> Value {
-    let mut machine = Machine::new(program);
-    machine.inputs.push_back(1);
-    machine.run_until_stopped().unwrap();
-    machine.outputs.pop_back().unwrap()
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> Value {
+    let machine = Machine::new(program).with_log(trace);
+    run_collecting(machine, &[1]).unwrap().pop().unwrap()
 }
 
 #[aoc(day9, part2)]
 fn part_2(program: &[Value]) -> Value {
-    let mut machine = Machine::new(program);
-    machine.inputs.push_back(2);
-    machine.run_until_stopped().unwrap();
-    machine.outputs.pop_back().unwrap()
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> Value {
+    let machine = Machine::new(program).with_log(trace);
+    run_collecting(machine, &[2]).unwrap().pop().unwrap()
+}
+
+#[derive(Default)]
+pub struct Day09 {
+    trace: bool,
+}
+
+impl Solver for Day09 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
 }
 
 #[cfg(test)]
@@ -41,4 +69,11 @@ mod tests {
         machine.run_until_stopped().unwrap();
         machine.outputs.into()
     }
+
+    #[test]
+    fn test_run_collecting() {
+        let program = parse("3,0,4,0,3,0,4,0,99").unwrap();
+        let outputs = run_collecting(Machine::new(&program), &[7, 11]).unwrap();
+        assert_eq!(outputs, [7, 11]);
+    }
 }
@@ -3,6 +3,8 @@ use std::collections::{HashMap, HashSet};
 
 use thiserror::Error;
 
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
 enum TileError {
     #[error("Invalid tile")]
@@ -40,37 +42,85 @@ fn part_1(map: &Map) -> usize {
 fn find_base_asteroid(map: &Map) -> (usize, (i32, i32)) {
     let mut max_visible = 0;
     let mut best_position = (0, 0);
-    let mut lines = HashSet::new();
-    for (i, &(x1, y1)) in map.asteroid_vec.iter().enumerate() {
-        lines.clear();
-        for (j, &(x2, y2)) in map.asteroid_vec.iter().enumerate() {
-            if j == i {
-                continue;
-            }
-            let mut dx = x2 - x1;
-            let mut dy = y2 - y1;
-            let scale = gcd(dx, dy);
-            dx /= scale;
-            dy /= scale;
-            lines.insert((dx, dy));
-        }
-        let visible = lines.len();
+    for &position in &map.asteroid_vec {
+        let visible = count_visible(map, position);
         if visible > max_visible {
             max_visible = visible;
-            best_position = (x1, y1);
+            best_position = position;
         }
     }
     (max_visible, best_position)
 }
 
+/// Number of asteroids with a direct line of sight from the given position.
+fn count_visible(map: &Map, (x1, y1): (i32, i32)) -> usize {
+    let mut lines = HashSet::new();
+    for &(x2, y2) in &map.asteroid_vec {
+        if (x2, y2) == (x1, y1) {
+            continue;
+        }
+        let mut dx = x2 - x1;
+        let mut dy = y2 - y1;
+        let scale = gcd(dx, dy);
+        dx /= scale;
+        dy /= scale;
+        lines.insert((dx, dy));
+    }
+    lines.len()
+}
+
 #[aoc(day10, part2)]
 fn part_2(map: &Map) -> i32 {
     let base_position = find_base_asteroid(map).1;
-    let (x, y) = find_nth_destroyed_asteroid(map, base_position, 200);
+    let (x, y) = vaporization_order(map, base_position)[199];
     100 * x + y
 }
 
-fn find_nth_destroyed_asteroid(map: &Map, (x0, y0): (i32, i32), nth: usize) -> (i32, i32) {
+pub struct Day10;
+
+impl Solver for Day10 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
+}
+
+#[allow(unused, reason = "tests")]
+fn find_nth_destroyed_asteroid(map: &Map, base: (i32, i32), nth: usize) -> (i32, i32) {
+    let mut all = firing_order_keys(map, base);
+    all.select_nth_unstable_by_key(nth - 1, |&(key, _)| key).1.1
+}
+
+/// Every asteroid other than the base, in the order the laser destroys them.
+fn vaporization_order(map: &Map, base: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut all = firing_order_keys(map, base);
+    all.sort_unstable_by_key(|&(key, _)| key);
+    all.into_iter().map(|(_, asteroid)| asteroid).collect()
+}
+
+/// Every asteroid other than the base, grouped by the rotation of the laser that destroys them.
+/// Each rotation only hits the nearest remaining asteroid in each direction, so the ones behind it
+/// survive until a later rotation.
+#[allow(unused, reason = "tests")]
+fn vaporization_frames(map: &Map, base: (i32, i32)) -> Vec<Vec<(i32, i32)>> {
+    let mut all = firing_order_keys(map, base);
+    all.sort_unstable_by_key(|&(key, _)| key);
+    let mut frames = Vec::<Vec<_>>::new();
+    for ((turn, _), asteroid) in all {
+        if turn == frames.len() {
+            frames.push(Vec::new());
+        }
+        frames[turn].push(asteroid);
+    }
+    frames
+}
+
+/// Each asteroid keyed by the rotation of the laser it is destroyed in, and its direction from
+/// the base.
+fn firing_order_keys(map: &Map, (x0, y0): (i32, i32)) -> Vec<((usize, Direction), (i32, i32))> {
     let mut lines = HashMap::<_, Vec<_>>::new();
     for &(x1, y1) in &map.asteroid_vec {
         let mut dx = x1 - x0;
@@ -83,35 +133,53 @@ fn find_nth_destroyed_asteroid(map: &Map, (x0, y0): (i32, i32), nth: usize) -> (
         dy /= scale;
         lines.entry((dx, dy)).or_default().push((x1, y1));
     }
-    let mut all = lines
+    lines
         .iter_mut()
         .flat_map(|(&(dx, dy), angle_group)| {
-            let angle = pseduo_angle(dx, dy);
+            let direction = Direction { dx, dy };
             angle_group.sort_unstable_by_key(|&(x1, y1)| {
-                (x1 - x0).unsigned_abs() + (y1 - x0).unsigned_abs()
+                (x1 - x0).unsigned_abs() + (y1 - y0).unsigned_abs()
             });
             // Index within the group is the turn it will get eliminated
             angle_group
                 .iter()
                 .enumerate()
-                .map(move |(turn, &asteroid)| ((turn, angle), asteroid))
+                .map(move |(turn, &asteroid)| ((turn, direction), asteroid))
         })
-        .collect::<Vec<_>>();
-    // f64 is not Ord, so have to use PartialOrd
-    all.select_nth_unstable_by(nth - 1, partial_cmp_first).1.1
+        .collect()
 }
 
-fn partial_cmp_first<K: PartialOrd, V>((x, _): &(K, V), (y, _): &(K, V)) -> Ordering {
-    x.partial_cmp(y).unwrap()
+/// Direction from the base, reduced so that `dx` and `dy` share no common factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Direction {
+    dx: i32,
+    dy: i32,
 }
 
-/// Same ordering as `f64::atan2(-f64::from(dx), f64::from(dy)) + std::f64::consts::PI`
-/// X-axis going right, and Y-axis going down. Negative Y-axis is zero, and increasing clockwise.
-fn pseduo_angle(dx: i32, dy: i32) -> f64 {
-    if dx >= 0 {
-        1.0 + f64::from(dy) / f64::from(dx.abs() + dy.abs())
-    } else {
-        3.0 - f64::from(dy) / f64::from(dx.abs() + dy.abs())
+impl Direction {
+    /// X-axis going right, and Y-axis going down. The half from the negative Y-axis clockwise
+    /// to just before the positive Y-axis is `0`, the rest is `1`.
+    const fn half(self) -> u8 {
+        if self.dx > 0 || (self.dx == 0 && self.dy < 0) {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Ordered by angle, with the negative Y-axis first, and increasing clockwise.
+impl Ord for Direction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Within a half, a positive cross product means `other` is clockwise of `self`
+        let cross = self.dx * other.dy - self.dy * other.dx;
+        self.half().cmp(&other.half()).then(0.cmp(&cross))
+    }
+}
+
+impl PartialOrd for Direction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -260,6 +328,17 @@ mod tests {
         find_base_asteroid(&map)
     }
 
+    #[test_case((1, 0) => 7)]
+    #[test_case((4, 0) => 7)]
+    #[test_case((0, 2) => 6)]
+    #[test_case((4, 2) => 5)]
+    #[test_case((3, 4) => 8)]
+    #[test_case((0, 0) => 8; "empty position")]
+    fn test_count_visible(from: (i32, i32)) -> usize {
+        let map = parse(EXAMPLE1).unwrap();
+        count_visible(&map, from)
+    }
+
     #[test_case(EXAMPLE6, (8, 3), 36 => (14, 3))]
     #[test_case(EXAMPLE5, (11, 13), 199 => (9, 6))]
     #[test_case(EXAMPLE5, (11, 13), 200 => (8, 2))]
@@ -268,4 +347,65 @@ mod tests {
         let map = parse(input).unwrap();
         find_nth_destroyed_asteroid(&map, base_position, nth)
     }
+
+    #[test]
+    fn test_direction_order() {
+        let clockwise = [
+            (0, -1),
+            (1, -3),
+            (1, -1),
+            (3, -1),
+            (1, 0),
+            (3, 1),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+            (-1, -3),
+        ]
+        .map(|(dx, dy)| Direction { dx, dy });
+        let mut sorted = clockwise;
+        sorted.reverse();
+        sorted.sort_unstable();
+        assert_eq!(sorted, clockwise);
+    }
+
+    #[test]
+    fn test_nearest_on_ray_first() {
+        let map = parse("#\n.\n#\n#").unwrap();
+        assert_eq!(find_nth_destroyed_asteroid(&map, (0, 3), 1), (0, 2));
+        assert_eq!(vaporization_order(&map, (0, 3)), [(0, 2), (0, 0)]);
+    }
+
+    #[test]
+    fn test_vaporization_order() {
+        let map = parse(EXAMPLE5).unwrap();
+        let order = vaporization_order(&map, (11, 13));
+        assert_eq!(order.len(), map.asteroid_vec.len() - 1);
+        assert_eq!(order[..3], [(11, 12), (12, 1), (12, 2)]);
+        assert_eq!(order[199], (8, 2));
+        assert_eq!(order.last(), Some(&(11, 1)));
+        for (ix, &asteroid) in order.iter().enumerate() {
+            assert_eq!(find_nth_destroyed_asteroid(&map, (11, 13), ix + 1), asteroid);
+        }
+    }
+
+    #[test_case(EXAMPLE5, (11, 13); "large example")]
+    #[test_case(EXAMPLE6, (8, 3); "rotation example")]
+    fn test_vaporization_frames(input: &str, base: (i32, i32)) {
+        let map = parse(input).unwrap();
+        let frames = vaporization_frames(&map, base);
+        let total = frames.iter().map(Vec::len).sum::<usize>();
+        assert_eq!(total, map.asteroid_vec.len() - 1);
+        assert_eq!(frames[0].len(), count_visible(&map, base));
+        assert!(frames.iter().all(|frame| !frame.is_empty()));
+        assert_eq!(frames.concat(), vaporization_order(&map, base));
+    }
+
+    #[test]
+    fn test_vaporization_frames_behind() {
+        let map = parse("#\n.\n#\n#").unwrap();
+        assert_eq!(vaporization_frames(&map, (0, 3)), [[(0, 2)], [(0, 0)]]);
+    }
 }
@@ -1,10 +1,12 @@
-use std::collections::HashMap;
 use std::num::ParseIntError;
 use std::ops::{Add, AddAssign};
 
 use thiserror::Error;
 
-use crate::machine::{Machine, MachineError, State, Value};
+use crate::geom::{Position, SparseGrid};
+use crate::machine::{Machine, MachineError, State, Value, parse_program};
+use crate::render;
+use crate::solver::{Answer, Solver};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 enum Direction {
@@ -44,18 +46,6 @@ enum AntError {
     MachineError(#[from] MachineError),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-struct Position {
-    x: i32,
-    y: i32,
-}
-
-impl Position {
-    const fn new(x: i32, y: i32) -> Self {
-        Self { x, y }
-    }
-}
-
 impl AddAssign<Direction> for Position {
     fn add_assign(&mut self, rhs: Direction) {
         match rhs {
@@ -115,7 +105,7 @@ impl TryFrom<Value> for PixelColor {
 
 #[derive(Debug, Clone, Default)]
 struct PainterAnt {
-    pixels: HashMap<Position, PixelColor>,
+    pixels: SparseGrid<PixelColor>,
     position: Position,
     direction: Direction,
 }
@@ -126,10 +116,7 @@ impl PainterAnt {
     }
 
     fn observe_camera(&self) -> PixelColor {
-        self.pixels
-            .get(&self.position)
-            .copied()
-            .unwrap_or(PixelColor::Black)
+        self.pixels.get(self.position)
     }
 
     fn turn(&mut self, turn: Turn) {
@@ -141,48 +128,99 @@ impl PainterAnt {
     }
 
     fn paint(&mut self, color: PixelColor) {
-        self.pixels.insert(self.position, color);
+        self.pixels.set(self.position, color);
+    }
+
+    /// Dense grid of the painted area, indexed as `grid[y][x]` with `true` for white, along with
+    /// the position of the top-left cell.
+    #[allow(unused, reason = "tests")]
+    fn to_grid(&self) -> (Vec<Vec<bool>>, Position) {
+        let Some((min, max)) = self.pixels.bounds() else {
+            return (Vec::new(), Position::default());
+        };
+        let grid = (min.y..=max.y)
+            .map(|y| {
+                (min.x..=max.x)
+                    .map(|x| self.pixels.get(Position::new(x, y)) == PixelColor::White)
+                    .collect()
+            })
+            .collect();
+        (grid, min)
     }
 
     fn render_image(&self) -> String {
-        let mut min_x = i32::MAX;
-        let mut max_x = i32::MIN;
-        let mut min_y = i32::MAX;
-        let mut max_y = i32::MIN;
-        for &pos in self.pixels.keys() {
-            min_x = min_x.min(pos.x);
-            max_x = max_x.max(pos.x);
-            min_y = min_y.min(pos.y);
-            max_y = max_y.max(pos.y);
-        }
-        let width = usize::try_from(max_x - min_x + 1).unwrap();
-        let height = usize::try_from(max_y - min_y + 1).unwrap();
-        let mut image = String::with_capacity((width + 1) * height.div_ceil(2));
-        for y in (min_y..=max_y).step_by(2) {
-            image.push('\n');
-            for x in min_x..=max_x {
-                let p1 = self
-                    .pixels
-                    .get(&Position::new(x, y))
-                    .copied()
-                    .unwrap_or(PixelColor::Black);
-                let p2 = self
-                    .pixels
-                    .get(&Position::new(x, y + 1))
-                    .copied()
-                    .unwrap_or(PixelColor::Black);
-                image.push(match (p1, p2) {
-                    (PixelColor::White, PixelColor::White) => '█',
-                    (PixelColor::White, PixelColor::Black) => '▀',
-                    (PixelColor::Black, PixelColor::White) => '▄',
-                    (PixelColor::Black, PixelColor::Black) => ' ',
-                });
-            }
+        render::monochrome(&self.pixels, |&color| color == PixelColor::White)
+    }
+
+    /// Reads the white pixels as capital letters in the Advent of Code font, which are four
+    /// pixels wide and six high, with one pixel of spacing. Falls back to `render_image` if any
+    /// glyph is not recognized.
+    ///
+    /// Letters like `J` start with blank columns, so the first white pixel is not always where
+    /// the first letter starts. Each alignment within the width of a letter is tried.
+    fn render_letters(&self) -> String {
+        let white = self
+            .pixels
+            .iter()
+            .filter(|&(_, &color)| color == PixelColor::White)
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>();
+        let (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) = (
+            white.iter().map(|pos| pos.x).min(),
+            white.iter().map(|pos| pos.x).max(),
+            white.iter().map(|pos| pos.y).min(),
+            white.iter().map(|pos| pos.y).max(),
+        ) else {
+            return self.render_image();
+        };
+        if max_y - min_y + 1 != 6 {
+            return self.render_image();
         }
-        image
+        let is_white = |x: i64, y: i64| self.pixels.get(Position::new(x, y)) == PixelColor::White;
+        let read_letters = |start_x: i64| {
+            (start_x..=max_x)
+                .step_by(5)
+                .map(|cell_x| {
+                    GLYPHS
+                        .iter()
+                        .find(|(_, rows)| {
+                            rows.iter().zip(min_y..).all(|(row, y)| {
+                                row.bytes()
+                                    .zip(cell_x..)
+                                    .all(|(ch, x)| (ch == b'#') == is_white(x, y))
+                            })
+                        })
+                        .map(|&(letter, _)| letter)
+                })
+                .collect::<Option<String>>()
+        };
+        (0..4)
+            .find_map(|shift| read_letters(min_x - shift))
+            .unwrap_or_else(|| self.render_image())
     }
 }
 
+/// Letters of the Advent of Code font, as far as they are known.
+const GLYPHS: [(char, [&str; 6]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
 struct AntController {
     machine: Machine,
     painter: PainterAnt,
@@ -199,16 +237,29 @@ impl AntController {
     fn step(&mut self) -> Result<(), AntError> {
         let color = self.painter.observe_camera();
         self.machine.inputs.push_back(color as Value);
-        if let Some(new_color) = self.machine.run_until_output()? {
+        if let Some(new_color) = self.next_output()? {
             self.painter.paint(new_color.try_into()?);
         }
-        if let Some(turn) = self.machine.run_until_output()? {
+        if let Some(turn) = self.next_output()? {
             self.painter.turn(turn.try_into()?);
         }
         Ok(())
     }
 
-    fn run_until_completion(&mut self) -> Result<usize, AntError> {
+    /// Next output from the robot, or `None` if it halted instead.
+    fn next_output(&mut self) -> Result<Option<Value>, AntError> {
+        match self.machine.run_until_output() {
+            Err(MachineError::Stopped) => Ok(None),
+            result => Ok(result?),
+        }
+    }
+
+    /// Runs the robot from a starting panel of the given color, and returns the number of panels
+    /// painted. The starting panel only counts as painted if its color had to be changed.
+    fn run_until_completion(&mut self, start_color: PixelColor) -> Result<usize, AntError> {
+        if self.painter.observe_camera() != start_color {
+            self.painter.paint(start_color);
+        }
         while self.machine.state() == State::Running {
             self.step()?;
         }
@@ -218,21 +269,53 @@ impl AntController {
 
 #[aoc_generator(day11)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
-    input.split(',').map(str::parse).collect()
+    parse_program(input)
 }
 
 #[aoc(day11, part1)]
 fn part_1(program: &[Value]) -> usize {
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> usize {
     let mut controller = AntController::new(program);
-    controller.run_until_completion().unwrap()
+    controller.machine.log = trace;
+    controller.run_until_completion(PixelColor::Black).unwrap()
 }
 
 #[aoc(day11, part2)]
 fn part_2(program: &[Value]) -> String {
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> String {
     let mut controller = AntController::new(program);
-    controller.painter.paint(PixelColor::White);
-    controller.run_until_completion().unwrap();
-    controller.painter.render_image()
+    controller.machine.log = trace;
+    controller.run_until_completion(PixelColor::White).unwrap();
+    controller.painter.render_letters()
+}
+
+#[derive(Default)]
+pub struct Day11 {
+    trace: bool,
+}
+
+impl Solver for Day11 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
 }
 
 #[cfg(test)]
@@ -250,4 +333,87 @@ mod tests {
         assert_eq!(ant.pixels.len(), 6);
         assert_eq!(ant.render_image(), "\n  █\n▀▀ ");
     }
+
+    fn paint_rows(ant: &mut PainterAnt, x0: i64, rows: &[&str]) {
+        for (row, y) in rows.iter().zip(0..) {
+            for (ch, x) in row.bytes().zip(x0..) {
+                let color = if ch == b'#' {
+                    PixelColor::White
+                } else {
+                    PixelColor::Black
+                };
+                ant.pixels.set(Position::new(x, y), color);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_letters() {
+        let mut ant = PainterAnt::new();
+        paint_rows(
+            &mut ant,
+            0,
+            &[".#..#.", ".#..#.", ".####.", ".#..#.", ".#..#.", ".#..#."],
+        );
+        assert_eq!(ant.render_letters(), "H");
+        paint_rows(
+            &mut ant,
+            6,
+            &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"],
+        );
+        assert_eq!(ant.render_letters(), "HR");
+    }
+
+    #[test]
+    fn test_render_letters_blank_first_column() {
+        let mut ant = PainterAnt::new();
+        paint_rows(
+            &mut ant,
+            0,
+            &[".###", "..#.", "..#.", "..#.", "..#.", ".###"],
+        );
+        paint_rows(
+            &mut ant,
+            5,
+            &["..##", "...#", "...#", "...#", "#..#", ".##."],
+        );
+        assert_eq!(ant.render_letters(), "IJ");
+    }
+
+    #[test]
+    fn test_render_letters_unknown_glyph() {
+        let mut ant = PainterAnt::new();
+        paint_rows(
+            &mut ant,
+            0,
+            &["####", "####", "####", "####", "####", "####"],
+        );
+        assert_eq!(ant.render_letters(), ant.render_image());
+    }
+
+    #[test]
+    fn test_to_grid() {
+        let mut ant = PainterAnt::new();
+        ant.pixels.set(Position::new(-1, 2), PixelColor::White);
+        ant.pixels.set(Position::new(1, 3), PixelColor::Black);
+        ant.pixels.set(Position::new(0, 3), PixelColor::White);
+        let (grid, origin) = ant.to_grid();
+        assert_eq!(origin, Position::new(-1, 2));
+        assert_eq!(grid, [[true, false, false], [false, true, false]]);
+    }
+
+    #[test]
+    fn test_start_color() {
+        // Paints the panel the color it already has, turns left, and halts
+        let program = parse("3,100,4,100,104,0,99").unwrap();
+        for start_color in [PixelColor::Black, PixelColor::White] {
+            let mut controller = AntController::new(&program);
+            let painted = controller.run_until_completion(start_color).unwrap();
+            assert_eq!(painted, 1);
+            assert_eq!(
+                controller.painter.pixels.get(Position::new(0, 0)),
+                start_color
+            );
+        }
+    }
 }
@@ -1,116 +1,9 @@
-use std::collections::HashSet;
 use std::fmt::Display;
-use std::num::ParseIntError;
-use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
-use std::str::FromStr;
-
-use thiserror::Error;
-
-#[derive(Debug, Error)]
-enum ParseError {
-    #[error("Syntax error")]
-    SyntaxError,
-    #[error(transparent)]
-    InvalidNumber(#[from] ParseIntError),
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-struct Vector {
-    x: i64,
-    y: i64,
-    z: i64,
-}
-
-impl Vector {
-    const fn normalized(mut self) -> Self {
-        self.x = self.x.signum();
-        self.y = self.y.signum();
-        self.z = self.z.signum();
-        self
-    }
-
-    const fn size(self) -> u64 {
-        self.x.unsigned_abs() + self.y.unsigned_abs() + self.z.unsigned_abs()
-    }
-}
-
-impl AddAssign for Vector {
-    fn add_assign(&mut self, rhs: Self) {
-        self.x += rhs.x;
-        self.y += rhs.y;
-        self.z += rhs.z;
-    }
-}
-
-impl Add for Vector {
-    type Output = Self;
-
-    fn add(mut self, rhs: Self) -> Self::Output {
-        self += rhs;
-        self
-    }
-}
-
-impl SubAssign for Vector {
-    fn sub_assign(&mut self, rhs: Self) {
-        self.x -= rhs.x;
-        self.y -= rhs.y;
-        self.z -= rhs.z;
-    }
-}
-
-impl Sub for Vector {
-    type Output = Self;
-
-    fn sub(mut self, rhs: Self) -> Self::Output {
-        self -= rhs;
-        self
-    }
-}
-
-impl MulAssign<i64> for Vector {
-    fn mul_assign(&mut self, rhs: i64) {
-        self.x *= rhs;
-        self.y *= rhs;
-        self.z *= rhs;
-    }
-}
-
-impl Mul<i64> for Vector {
-    type Output = Self;
-
-    fn mul(mut self, rhs: i64) -> Self::Output {
-        self *= rhs;
-        self
-    }
-}
-
-impl FromStr for Vector {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (x, rest) = s
-            .strip_prefix("<x=")
-            .ok_or(ParseError::SyntaxError)?
-            .split_once(", y=")
-            .ok_or(ParseError::SyntaxError)?;
-        let x = x.parse()?;
-        let (y, rest) = rest.split_once(", z=").ok_or(ParseError::SyntaxError)?;
-        let y = y.parse()?;
-        let z = rest
-            .strip_suffix(">")
-            .ok_or(ParseError::SyntaxError)?
-            .parse()?;
-        Ok(Self { x, y, z })
-    }
-}
+use std::mem;
 
-impl Display for Vector {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let &Self { x, y, z } = self;
-        write!(f, "<x={x:2}, y={y:2}, z={z:2}>")
-    }
-}
+use crate::cycle::find_cycle;
+use crate::geom::{ParseVectorError, Vec3 as Vector};
+use crate::solver::{Answer, Solver};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Moon {
@@ -130,10 +23,18 @@ impl Moon {
         self.velocity += (other.position - self.position).normalized();
     }
 
+    fn undo_gravity(&mut self, other: &Self) {
+        self.velocity -= (other.position - self.position).normalized();
+    }
+
     fn apply_velocity(&mut self) {
         self.position += self.velocity;
     }
 
+    fn undo_velocity(&mut self) {
+        self.position -= self.velocity;
+    }
+
     const fn energy(&self) -> u64 {
         self.position.size() * self.velocity.size()
     }
@@ -147,21 +48,22 @@ impl Display for Moon {
 }
 
 #[derive(Debug, Clone)]
-struct Simulation<const N: usize> {
-    moons: [Moon; N],
-    time: u64,
+struct Simulation {
+    moons: Vec<Moon>,
+    /// Steps since the initial state. Negative after stepping back past it.
+    time: i64,
 }
 
-impl<const N: usize> Simulation<N> {
+impl Simulation {
     fn new(moons: &[Moon]) -> Self {
         Self {
-            moons: moons.to_vec().try_into().unwrap(),
+            moons: moons.to_vec(),
             time: 0,
         }
     }
 
     fn apply_gravity(&mut self) {
-        for i in 0..N {
+        for i in 0..self.moons.len() {
             let mut moon1 = self.moons[i]; // Copy
             for (j, moon2) in self.moons.iter().enumerate() {
                 if i == j {
@@ -173,24 +75,52 @@ impl<const N: usize> Simulation<N> {
         }
     }
 
+    fn undo_gravity(&mut self) {
+        for i in 0..self.moons.len() {
+            let mut moon1 = self.moons[i]; // Copy
+            for (j, moon2) in self.moons.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                moon1.undo_gravity(moon2);
+            }
+            self.moons[i] = moon1; // Put back
+        }
+    }
+
     fn apply_velocity(&mut self) {
         for moon in &mut self.moons {
             moon.apply_velocity();
         }
     }
 
+    fn undo_velocity(&mut self) {
+        for moon in &mut self.moons {
+            moon.undo_velocity();
+        }
+    }
+
     fn time_step(&mut self) {
         self.apply_gravity();
         self.apply_velocity();
         self.time += 1;
     }
 
+    /// Reverses `time_step`. Gravity only depends on the positions, so it can be undone once the
+    /// positions are restored.
+    #[allow(unused, reason = "tests")]
+    fn time_step_back(&mut self) {
+        self.undo_velocity();
+        self.undo_gravity();
+        self.time -= 1;
+    }
+
     fn total_energy(&self) -> u64 {
         self.moons.iter().map(Moon::energy).sum()
     }
 }
 
-impl<const N: usize> Display for Simulation<N> {
+impl Display for Simulation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self { time, moons } = self;
         writeln!(f, "After {time} steps:")?;
@@ -202,7 +132,7 @@ impl<const N: usize> Display for Simulation<N> {
 }
 
 #[aoc_generator(day12)]
-fn parse(input: &str) -> Result<Vec<Moon>, ParseError> {
+fn parse(input: &str) -> Result<Vec<Moon>, ParseVectorError> {
     input
         .lines()
         .map(|l| str::parse(l).map(Moon::new))
@@ -215,7 +145,7 @@ fn part_1(moons: &[Moon]) -> u64 {
 }
 
 fn total_energy_after(moons: &[Moon], time: u64) -> u64 {
-    let mut sim = Simulation::<4>::new(moons);
+    let mut sim = Simulation::new(moons);
     for _ in 0..time {
         sim.time_step();
     }
@@ -230,13 +160,42 @@ fn part_2(moons: &[Moon]) -> u64 {
     lcm(lcm(cycle_x, cycle_y), cycle_z)
 }
 
+pub struct Day12;
+
+impl Solver for Day12 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
+}
+
+/// Number of steps until the moons return to their initial state along one axis. The axes
+/// evolve independently, and since the simulation can be reversed, the initial state is always
+/// part of the cycle.
 fn find_time_until_repeat_slice(moons: &[Moon], view: impl Fn(Vector) -> i64) -> u64 {
-    let mut sim = Simulation::<4>::new(moons);
-    let mut seen = HashSet::new();
-    while seen.insert(sim.moons.map(|m| (view(m.position), view(m.velocity)))) {
+    let project = |v: Vector| Vector {
+        x: view(v),
+        ..Vector::default()
+    };
+    let start = moons
+        .iter()
+        .map(|m| Moon {
+            position: project(m.position),
+            velocity: project(m.velocity),
+        })
+        .collect::<Vec<_>>();
+    let cycle = find_cycle(&start, |moons| {
+        let mut sim = Simulation {
+            moons: mem::take(moons),
+            time: 0,
+        };
         sim.time_step();
-    }
-    sim.time
+        *moons = sim.moons;
+    });
+    cycle.length as u64
 }
 
 const fn lcm(u: u64, v: u64) -> u64 {
@@ -324,6 +283,46 @@ mod tests {
         total_energy_after(&moons, time)
     }
 
+    #[test_case(EXAMPLE1, 10 => 179)]
+    #[test_case("<x=-1, y=0, z=2>\n<x=2, y=-10, z=-7>\n<x=4, y=-8, z=8>", 10 => 190)]
+    #[test_case("<x=-1, y=0, z=2>\n<x=2, y=-10, z=-7>\n<x=4, y=-8, z=8>\n<x=3, y=5, z=-1>\n<x=0, y=0, z=0>", 10 => 255)]
+    fn test_other_moon_counts(input: &str, time: u64) -> u64 {
+        let moons = parse(input).unwrap();
+        total_energy_after(&moons, time)
+    }
+
+    #[test_case(EXAMPLE1)]
+    #[test_case(EXAMPLE2)]
+    fn test_time_step_back(input: &str) {
+        let moons = parse(input).unwrap();
+        let mut sim = Simulation::new(&moons);
+        for _ in 0..100 {
+            sim.time_step();
+        }
+        assert_ne!(sim.moons, moons);
+        for _ in 0..100 {
+            sim.time_step_back();
+        }
+        assert_eq!(sim.time, 0);
+        assert_eq!(sim.moons, moons);
+    }
+
+    #[test_case(EXAMPLE1)]
+    #[test_case(EXAMPLE2)]
+    fn test_time_step_back_before_start(input: &str) {
+        let moons = parse(input).unwrap();
+        let mut sim = Simulation::new(&moons);
+        for _ in 0..10 {
+            sim.time_step_back();
+        }
+        assert_eq!(sim.time, -10);
+        for _ in 0..10 {
+            sim.time_step();
+        }
+        assert_eq!(sim.time, 0);
+        assert_eq!(sim.moons, moons);
+    }
+
     #[test_case(EXAMPLE1 => 2_772)]
     #[test_case(EXAMPLE2 => 4_686_774_924)]
     fn test_part_2(input: &str) -> u64 {
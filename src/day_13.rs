@@ -1,10 +1,12 @@
-use std::collections::HashMap;
 use std::fmt::Display;
 use std::num::ParseIntError;
 
 use thiserror::Error;
 
+use crate::geom::SparseGrid;
 use crate::machine::{parse_program, Machine, MachineError, Value};
+use crate::render::{AnsiGrid, Color};
+use crate::solver::{Answer, Solver};
 
 #[derive(Debug, Error)]
 enum RuntimeError {
@@ -18,8 +20,9 @@ enum RuntimeError {
     MachineError(#[from] MachineError),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 enum Tile {
+    #[default]
     Empty = 0,
     Wall = 1,
     Block = 2,
@@ -27,43 +30,14 @@ enum Tile {
     Ball = 4,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-#[allow(unused)]
-enum AnsiColor {
-    Black = 0,
-    Red,
-    Green,
-    Yellow,
-    Blue,
-    Purple,
-    Cyan,
-    White,
-    Reset,
-}
-
-impl Display for AnsiColor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if matches!(self, Self::Reset) {
-            write!(f, "\x1b[0m")
-        } else if f.alternate() {
-            let n = *self as u8;
-            write!(f, "\x1b[3{n}m")
-        } else {
-            let n = *self as u8;
-            write!(f, "\x1b[4{n}m")
-        }
-    }
-}
-
 impl Tile {
-    const fn color(self) -> AnsiColor {
+    const fn color(self) -> Color {
         match self {
-            Self::Empty => AnsiColor::Black,
-            Self::Wall => AnsiColor::White,
-            Self::Block => AnsiColor::Purple,
-            Self::HorizontalPaddle => AnsiColor::Yellow,
-            Self::Ball => AnsiColor::Blue,
+            Self::Empty => Color::Black,
+            Self::Wall => Color::White,
+            Self::Block => Color::Purple,
+            Self::HorizontalPaddle => Color::Yellow,
+            Self::Ball => Color::Blue,
         }
     }
 }
@@ -83,26 +57,9 @@ impl TryFrom<Value> for Tile {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Position {
-    x: Value,
-    y: Value,
-}
-impl Position {
-    const fn new(x: Value, y: Value) -> Self {
-        Self { x, y }
-    }
-}
-
-impl From<(Value, Value)> for Position {
-    fn from((x, y): (Value, Value)) -> Self {
-        Self { x, y }
-    }
-}
-
 #[derive(Debug, Clone, Default)]
 struct Screen {
-    tiles: HashMap<Position, Tile>,
+    tiles: SparseGrid<Tile>,
 }
 
 impl Screen {
@@ -111,32 +68,15 @@ impl Screen {
     }
 
     fn set_tile(&mut self, x: Value, y: Value, tile: Tile) {
-        self.tiles.insert((x, y).into(), tile);
+        self.tiles.set((x, y).into(), tile);
     }
 }
 
+/// Shows every tile the game has drawn so far, so the size of the board follows the program.
 impl Display for Screen {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in (0..20).step_by(2) {
-            writeln!(f)?;
-            for x in 0..44 {
-                let tile1 = self
-                    .tiles
-                    .get(&Position::new(x, y))
-                    .copied()
-                    .unwrap_or(Tile::Empty)
-                    .color();
-                let tile2 = self
-                    .tiles
-                    .get(&Position::new(x, y + 1))
-                    .copied()
-                    .unwrap_or(Tile::Empty)
-                    .color();
-                write!(f, "{tile1}{tile2:#}▀")?;
-            }
-            write!(f, "{}", AnsiColor::Reset)?;
-        }
-        Ok(())
+        let colors = self.tiles.map(|&tile| tile.color());
+        write!(f, "{}", AnsiGrid::new(&colors))
     }
 }
 
@@ -145,7 +85,10 @@ struct Arcade {
     controller: Machine,
     screen: Screen,
     score: Value,
-    log: bool,
+    ball_x: Option<Value>,
+    paddle_x: Option<Value>,
+    recording: Option<Vec<Value>>,
+    block_history: Option<Vec<usize>>,
 }
 
 impl Arcade {
@@ -154,7 +97,10 @@ impl Arcade {
             controller: Machine::new(program),
             screen: Screen::new(),
             score: 0,
-            log: false,
+            ball_x: None,
+            paddle_x: None,
+            recording: None,
+            block_history: None,
         }
     }
 
@@ -166,7 +112,13 @@ impl Arcade {
             if (x, y) == (-1, 0) {
                 self.score = tile;
             } else {
-                self.screen.set_tile(x, y, tile.try_into()?);
+                let tile = tile.try_into()?;
+                match tile {
+                    Tile::Ball => self.ball_x = Some(x),
+                    Tile::HorizontalPaddle => self.paddle_x = Some(x),
+                    _ => (),
+                }
+                self.screen.set_tile(x, y, tile);
             }
         }
         Ok(())
@@ -180,42 +132,93 @@ impl Arcade {
             .count()
     }
 
-    fn play(&mut self) -> Result<(), RuntimeError> {
-        let mut first = true;
+    #[allow(unused, reason = "tests")]
+    fn blocks_remaining(&self) -> usize {
+        self.count_blocks()
+    }
+
+    /// Starts collecting the number of blocks remaining after each frame.
+    #[allow(unused, reason = "tests")]
+    fn track_blocks(&mut self) {
+        self.block_history = Some(Vec::new());
+    }
+
+    /// Runs the game until it needs input or halts.
+    fn frame(&mut self) -> RuntimeError {
+        let err = self.tick().unwrap_err();
+        if let Some(mut history) = self.block_history.take() {
+            history.push(self.count_blocks());
+            self.block_history = Some(history);
+        }
+        err
+    }
+
+    /// Moves the paddle towards the ball.
+    fn joystick(&self) -> Result<Value, RuntimeError> {
+        let ball_x = self.ball_x.ok_or(RuntimeError::MissingBall)?;
+        let paddle_x = self.paddle_x.ok_or(RuntimeError::MissingPaddle)?;
+        Ok((ball_x - paddle_x).signum())
+    }
+
+    /// Starts recording the joystick inputs chosen by the AI.
+    #[allow(unused, reason = "tests")]
+    fn record_inputs(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    fn push_joystick(&mut self) -> Result<(), RuntimeError> {
+        let input = self.joystick()?;
+        if let Some(recording) = &mut self.recording {
+            recording.push(input);
+        }
+        self.controller.inputs.push_back(input);
+        Ok(())
+    }
+
+    /// Plays until the game halts, and returns the final score.
+    fn run_headless(&mut self) -> Result<Value, RuntimeError> {
         loop {
-            match self.tick().unwrap_err() {
-                RuntimeError::MachineError(MachineError::Stopped) => {
-                    return Ok(())
-                }
+            match self.frame() {
+                RuntimeError::MachineError(MachineError::Stopped) => return Ok(self.score),
+                RuntimeError::MachineError(MachineError::EmptyInput) => self.push_joystick()?,
+                e => Err(e)?,
+            }
+        }
+    }
+
+    /// Like `run_headless`, but draws the screen to the terminal every time the game waits for
+    /// input.
+    #[allow(unused, reason = "debugging")]
+    fn run_with_display(&mut self) -> Result<Value, RuntimeError> {
+        // Lines the cursor moved down while drawing the previous frame
+        let mut drawn_lines = 0;
+        loop {
+            match self.frame() {
+                RuntimeError::MachineError(MachineError::Stopped) => return Ok(self.score),
                 RuntimeError::MachineError(MachineError::EmptyInput) => {
-                    if self.log {
-                        if first {
-                            first = false;
-                        } else {
-                            print!("\x1b[11A");
-                        }
-                        println!("{}", &self.screen);
+                    if drawn_lines > 0 {
+                        print!("\x1b[{drawn_lines}A");
                     }
-                    let ball_x = self
-                        .screen
-                        .tiles
-                        .iter()
-                        .find_map(|(&pos, &tile)| (tile == Tile::Ball).then_some(pos.x))
-                        .ok_or(RuntimeError::MissingBall)?;
-                    let paddle_x = self
-                        .screen
-                        .tiles
-                        .iter()
-                        .find_map(|(&pos, &tile)| (tile == Tile::HorizontalPaddle).then_some(pos.x))
-                        .ok_or(RuntimeError::MissingPaddle)?;
-                    self.controller
-                        .inputs
-                        .push_back((ball_x - paddle_x).signum());
+                    let frame = self.screen.to_string();
+                    println!("{frame}");
+                    drawn_lines = frame.matches('\n').count() + 1;
+                    self.push_joystick()?;
                 }
                 e => Err(e)?,
             }
         }
     }
+
+    /// Plays with a fixed sequence of joystick inputs instead of the AI, and returns the final
+    /// score. Fails with `EmptyInput` if the game outlasts the script.
+    #[allow(unused, reason = "tests")]
+    fn replay(&mut self, inputs: &[Value]) -> Result<Value, RuntimeError> {
+        self.controller.inputs.extend(inputs);
+        match self.frame() {
+            RuntimeError::MachineError(MachineError::Stopped) => Ok(self.score),
+            e => Err(e),
+        }
+    }
 }
 
 #[aoc_generator(day13)]
@@ -225,17 +228,108 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day13, part1)]
 fn part_1(program: &[Value]) -> usize {
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> usize {
     let mut arcade = Arcade::new(program);
-    arcade.play().unwrap();
+    arcade.controller.log = trace;
+    arcade.run_headless().unwrap();
     arcade.count_blocks()
 }
 
 #[aoc(day13, part2)]
 fn part_2(program: &[Value]) -> Value {
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> Value {
     let mut arcade = Arcade::new(program);
+    arcade.controller.log = trace;
     arcade.controller.write(0, 2);
-    arcade.play().unwrap();
-    arcade.score
+    arcade.run_headless().unwrap()
 }
 
-// No test cases
\ No newline at end of file
+#[derive(Default)]
+pub struct Day13 {
+    trace: bool,
+}
+
+impl Solver for Day13 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws a ball at x=1 and the paddle at x=0, then reports the joystick input as the score
+    const GAME: &str = "104,1,104,0,104,4,104,0,104,0,104,3,3,100,104,-1,104,0,4,100,99";
+
+    #[test]
+    fn test_run_headless() {
+        let program = parse(GAME).unwrap();
+        let mut arcade = Arcade::new(&program);
+        assert_eq!(arcade.run_headless().unwrap(), 1);
+        assert_eq!(arcade.ball_x, Some(1));
+        assert_eq!(arcade.paddle_x, Some(0));
+    }
+
+    #[test]
+    fn test_screen_display() {
+        let program = parse(GAME).unwrap();
+        let mut arcade = Arcade::new(&program);
+        assert_eq!(arcade.screen.to_string(), "");
+        arcade.run_headless().unwrap();
+        assert_eq!(
+            arcade.screen.to_string(),
+            "\n\x1b[33m\x1b[40m▀\x1b[34m\x1b[40m▀\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let program = parse(GAME).unwrap();
+        let mut arcade = Arcade::new(&program);
+        arcade.record_inputs();
+        let score = arcade.run_headless().unwrap();
+        let recording = arcade.recording.unwrap();
+        assert_eq!(recording, [1]);
+
+        let mut arcade = Arcade::new(&program);
+        assert_eq!(arcade.replay(&recording).unwrap(), score);
+        let mut arcade = Arcade::new(&program);
+        assert_eq!(arcade.replay(&[-1]).unwrap(), -1);
+        let mut arcade = Arcade::new(&program);
+        assert!(arcade.replay(&[]).is_err());
+    }
+
+    #[test]
+    fn test_block_history() {
+        // Draws two blocks, then clears one after each input
+        let program = parse(
+            "104,2,104,0,104,2,104,3,104,0,104,2,104,1,104,1,104,4,104,0,104,1,104,3,\
+             3,200,104,2,104,0,104,0,3,200,104,3,104,0,104,0,99",
+        )
+        .unwrap();
+        let mut arcade = Arcade::new(&program);
+        arcade.track_blocks();
+        arcade.run_headless().unwrap();
+        assert_eq!(arcade.block_history, Some(vec![2, 1, 0]));
+        assert_eq!(arcade.blocks_remaining(), 0);
+    }
+}
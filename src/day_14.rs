@@ -4,6 +4,8 @@ use std::str::FromStr;
 
 use thiserror::Error;
 
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
 enum ParseError {
     #[error("Syntax error")]
@@ -27,6 +29,14 @@ impl Chemical {
             Self::Other(ix) => ix,
         }
     }
+
+    const fn from_index(ix: usize) -> Self {
+        match ix {
+            0 => Self::Ore,
+            1 => Self::Fuel,
+            _ => Self::Other(ix),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -81,6 +91,46 @@ impl FromStr for ReactionList {
     }
 }
 
+impl ReactionList {
+    /// Every chemical, ordered so that each reaction comes before the reactions producing its
+    /// inputs, with ORE last. Returns `None` if some chemical is needed to produce itself.
+    #[allow(unused, reason = "tests")]
+    fn topo_order(&self) -> Option<Vec<Chemical>> {
+        let mut consumers = vec![0_usize; self.num_chemicals];
+        for reaction in &self.reactions {
+            for &(_, chem) in &reaction.requires {
+                consumers[chem.index()] += 1;
+            }
+        }
+        let mut lookup = vec![None; self.num_chemicals];
+        for reaction in &self.reactions {
+            lookup[reaction.produces.index()] = Some(reaction);
+        }
+        let mut pending = (0..self.num_chemicals)
+            .filter(|&ix| ix != Chemical::Ore.index() && consumers[ix] == 0)
+            .map(Chemical::from_index)
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(self.num_chemicals);
+        while let Some(chem) = pending.pop_front() {
+            order.push(chem);
+            let Some(reaction) = lookup[chem.index()] else {
+                continue;
+            };
+            for &(_, chem2) in &reaction.requires {
+                consumers[chem2.index()] -= 1;
+                if consumers[chem2.index()] == 0 && chem2 != Chemical::Ore {
+                    pending.push_back(chem2);
+                }
+            }
+        }
+        if consumers[Chemical::Ore.index()] != 0 || order.len() + 1 != self.num_chemicals {
+            return None;
+        }
+        order.push(Chemical::Ore);
+        Some(order)
+    }
+}
+
 #[aoc_generator(day14)]
 fn parse(input: &str) -> Result<ReactionList, ParseError> {
     input.parse()
@@ -93,10 +143,26 @@ fn part_1(list: &ReactionList) -> u64 {
 
 #[aoc(day14, part2)]
 fn part_2(list: &ReactionList) -> u64 {
-    let target = 1_000_000_000_000_u64;
+    max_fuel_for_ore(list, 1_000_000_000_000)
+}
+
+pub struct Day14;
+
+impl Solver for Day14 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
+}
+
+/// Largest amount of fuel that can be produced without using more than `target` ore.
+fn max_fuel_for_ore(list: &ReactionList, target: u64) -> u64 {
     let one_fuel = ore_to_produce_fuel(list, 1);
     let mut high = target.div_ceil(one_fuel) * 2;
-    let mut low = 1;
+    let mut low = 0;
     while low < high {
         let mid = (low + high).div_ceil(2);
         let result = ore_to_produce_fuel(list, mid);
@@ -109,7 +175,72 @@ fn part_2(list: &ReactionList) -> u64 {
     low
 }
 
+/// Largest amount of fuel that can be produced for each of the ore `budgets`.
+///
+/// The ore needed grows almost linearly with the fuel, so each search starts from an estimate
+/// based on the largest answer so far, and widens from there until the answer is bracketed. The
+/// ore needed for each amount of fuel is remembered between the searches.
+#[allow(unused, reason = "tests")]
+fn fuel_curve(list: &ReactionList, budgets: &[u64]) -> Vec<u64> {
+    let mut memo = HashMap::new();
+    let mut ore = |fuel: u64| {
+        *memo
+            .entry(fuel)
+            .or_insert_with(|| ore_to_produce_fuel(list, fuel))
+    };
+    let mut reference = (1, ore(1));
+    let mut curve = Vec::with_capacity(budgets.len());
+    for &budget in budgets {
+        let (ref_fuel, ref_ore) = reference;
+        let estimate = u128::from(budget) * u128::from(ref_fuel) / u128::from(ref_ore);
+        let estimate = u64::try_from(estimate).unwrap_or(u64::MAX);
+        // Invariant: ore(low) <= budget < ore(high)
+        let (mut low, mut high);
+        let mut step = 1;
+        if ore(estimate) <= budget {
+            low = estimate;
+            loop {
+                high = low + step;
+                if ore(high) > budget {
+                    break;
+                }
+                low = high;
+                step *= 2;
+            }
+        } else {
+            high = estimate;
+            loop {
+                low = high.saturating_sub(step);
+                if ore(low) <= budget {
+                    break;
+                }
+                high = low;
+                step *= 2;
+            }
+        }
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if ore(mid) <= budget {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        if low > ref_fuel {
+            reference = (low, ore(low));
+        }
+        curve.push(low);
+    }
+    curve
+}
+
 fn ore_to_produce_fuel(list: &ReactionList, num_fuel: u64) -> u64 {
+    production_report(list, num_fuel).0
+}
+
+/// Ore required to produce the fuel, along with the surplus of every chemical that had some
+/// left over afterwards.
+fn production_report(list: &ReactionList, num_fuel: u64) -> (u64, Vec<(Chemical, u64)>) {
     let mut lookup = vec![None; list.num_chemicals];
     for reaction in &list.reactions {
         lookup[reaction.produces.index()] = Some(reaction);
@@ -134,7 +265,13 @@ fn ore_to_produce_fuel(list: &ReactionList, num_fuel: u64) -> u64 {
             leftovers[chem.index()] -= qty;
         }
     }
-    ores
+    let surplus = leftovers
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, qty)| qty > 0)
+        .map(|(ix, qty)| (Chemical::from_index(ix), qty))
+        .collect();
+    (ores, surplus)
 }
 
 #[cfg(test)]
@@ -230,12 +367,12 @@ mod tests {
         assert_eq!(
             result.reactions,
             [
+                reaction!(7 A, 1 E => 1 FUEL),
                 reaction!(10 ORE => 10 A),
                 reaction!(1 ORE => 1 B),
                 reaction!(7 A, 1 B => 1 C),
                 reaction!(7 A, 1 C => 1 D),
                 reaction!(7 A, 1 D => 1 E),
-                reaction!(7 A, 1 E => 1 FUEL),
             ]
         );
     }
@@ -250,6 +387,13 @@ mod tests {
         part_1(&list)
     }
 
+    #[test]
+    fn test_production_report() {
+        let list = parse(EXAMPLE1).unwrap();
+        assert_eq!(production_report(&list, 1), (31, vec![(Chemical::Other(2), 2)]));
+        assert_eq!(production_report(&list, 10), (290, vec![]));
+    }
+
     #[test_case(EXAMPLE3 => 82_892_753)]
     #[test_case(EXAMPLE4 => 5_586_022)]
     #[test_case(EXAMPLE5 => 460_664)]
@@ -257,4 +401,65 @@ mod tests {
         let list = parse(input).unwrap();
         part_2(&list)
     }
+
+    #[test]
+    fn test_topo_order() {
+        let list = parse(EXAMPLE2).unwrap();
+        let order = list.topo_order().unwrap();
+        assert_eq!(order.len(), list.num_chemicals);
+        assert_eq!(order.first(), Some(&Chemical::Fuel));
+        assert_eq!(order.last(), Some(&Chemical::Ore));
+        let rank = |chem| order.iter().position(|&c| c == chem).unwrap();
+        for reaction in &list.reactions {
+            for &(_, chem) in &reaction.requires {
+                assert!(rank(reaction.produces) < rank(chem));
+            }
+        }
+    }
+
+    #[test]
+    fn test_topo_order_cyclic() {
+        let list = parse("1 ORE, 1 B => 1 A\n1 A => 1 B\n1 A => 1 FUEL").unwrap();
+        assert_eq!(list.topo_order(), None);
+    }
+
+    #[test_case(EXAMPLE1, 30 => 0)]
+    #[test_case(EXAMPLE1, 31 => 1)]
+    #[test_case(EXAMPLE1, 300 => 10)]
+    #[test_case(EXAMPLE3, 500_000_000_000 => 41_446_376)]
+    fn test_max_fuel_for_ore(input: &str, target: u64) -> u64 {
+        let list = parse(input).unwrap();
+        max_fuel_for_ore(&list, target)
+    }
+
+    #[test_case(EXAMPLE1; "small")]
+    #[test_case(EXAMPLE3; "large")]
+    #[test_case(EXAMPLE5; "largest")]
+    fn test_fuel_curve(input: &str) {
+        let list = parse(input).unwrap();
+        let budgets = [
+            1_000_000_000_000,
+            0,
+            30,
+            31,
+            300,
+            12_345_678,
+            500_000_000_000,
+            999_999_999_999,
+        ];
+        let expected = budgets
+            .iter()
+            .map(|&budget| max_fuel_for_ore(&list, budget))
+            .collect::<Vec<_>>();
+        assert_eq!(fuel_curve(&list, &budgets), expected);
+    }
+
+    #[test]
+    fn test_fuel_curve_part_2() {
+        let list = parse(EXAMPLE3).unwrap();
+        assert_eq!(
+            fuel_curve(&list, &[500_000_000_000, 1_000_000_000_000]),
+            [41_446_376, 82_892_753]
+        );
+    }
 }
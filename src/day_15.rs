@@ -5,6 +5,7 @@ use std::ops::{Add, AddAssign};
 use thiserror::Error;
 
 use crate::machine::{parse_program, Machine, MachineError, Value};
+use crate::solver::{Answer, Solver};
 
 #[derive(Debug, Error)]
 enum RuntimeError {
@@ -175,6 +176,96 @@ impl Map {
         None
     }
 
+    /// Moves leading from the start to the goal along a shortest path.
+    #[allow(unused, reason = "tests")]
+    fn path_to_goal(&self) -> Option<Vec<Direction>> {
+        let start_position = Position::default();
+        let mut pending = VecDeque::new();
+        pending.push_back(start_position);
+        let mut came_from = HashMap::new();
+        let mut visited = HashSet::new();
+        visited.insert(start_position);
+        while let Some(pos) = pending.pop_front() {
+            match self.get(pos) {
+                Tile::Wall => continue,
+                Tile::Unknown => return None,
+                Tile::Open => {}
+                Tile::Goal => {
+                    let mut path = Vec::new();
+                    let mut pos = pos;
+                    while let Some(&(prev, dir)) = came_from.get(&pos) {
+                        path.push(dir);
+                        pos = prev;
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+            }
+            for dir in Direction::all() {
+                if visited.insert(pos + dir) {
+                    came_from.insert(pos + dir, (pos, dir));
+                    pending.push_back(pos + dir);
+                }
+            }
+        }
+        None
+    }
+
+    /// Cells reached by the oxygen each minute, starting with the goal itself at minute zero.
+    /// Returns an empty list if the goal has not been found.
+    #[allow(unused, reason = "tests")]
+    fn oxygen_spread(&self) -> Vec<HashSet<Position>> {
+        let Some(goal) = self.goal else {
+            return Vec::new();
+        };
+        let mut visited = HashSet::from([goal]);
+        let mut frontier = HashSet::from([goal]);
+        let mut minutes = Vec::new();
+        while !frontier.is_empty() {
+            let mut next = HashSet::new();
+            for &pos in &frontier {
+                for dir in Direction::all() {
+                    let pos2 = pos + dir;
+                    if self.get(pos2) == Tile::Open && visited.insert(pos2) {
+                        next.insert(pos2);
+                    }
+                }
+            }
+            minutes.push(frontier);
+            frontier = next;
+        }
+        minutes
+    }
+
+    /// Draws the known part of the map, with the droid at `droid`.
+    #[allow(unused, reason = "debugging")]
+    fn render(&self, droid: Position) -> String {
+        let min_x = self.tiles.keys().map(|pos| pos.x).min().unwrap_or(0);
+        let max_x = self.tiles.keys().map(|pos| pos.x).max().unwrap_or(0);
+        let min_y = self.tiles.keys().map(|pos| pos.y).min().unwrap_or(0);
+        let max_y = self.tiles.keys().map(|pos| pos.y).max().unwrap_or(0);
+        let mut image = String::new();
+        for y in min_y..=max_y {
+            if y != min_y {
+                image.push('\n');
+            }
+            for x in min_x..=max_x {
+                let pos = Position { x, y };
+                image.push(if pos == droid {
+                    'D'
+                } else {
+                    match self.get(pos) {
+                        Tile::Unknown => ' ',
+                        Tile::Open => '.',
+                        Tile::Wall => '#',
+                        Tile::Goal => 'G',
+                    }
+                });
+            }
+        }
+        image
+    }
+
     fn longest_distance_from_goal(&self) -> Option<usize> {
         let start_position = self.goal?;
         let mut pending = VecDeque::new();
@@ -251,18 +342,51 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day15, part1)]
 fn part_1(program: &[Value]) -> usize {
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> usize {
     let mut droid = RepairDroid::new(program);
+    droid.controller.log = trace;
     droid.explore().unwrap();
     droid.map.shortest_distance_to_goal().unwrap()
 }
 
 #[aoc(day15, part2)]
 fn part_2(program: &[Value]) -> usize {
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> usize {
     let mut droid = RepairDroid::new(program);
+    droid.controller.log = trace;
     droid.explore().unwrap();
     droid.map.longest_distance_from_goal().unwrap()
 }
 
+#[derive(Default)]
+pub struct Day15 {
+    trace: bool,
+}
+
+impl Solver for Day15 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -275,9 +399,8 @@ mod tests {
         ?###??\
     ";
 
-    #[test]
     #[expect(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
-    fn test_map() {
+    fn example_map() -> Map {
         let mut map = Map::new();
         let mut origin = Position::default();
         'y: for (y, line) in EXAMPLE_MAP.lines().enumerate() {
@@ -306,9 +429,41 @@ mod tests {
                 }
             }
         }
+        map
+    }
 
+    #[test]
+    fn test_map() {
+        let map = example_map();
         assert_eq!(map.direction_of_nearest_unknown(Position::default()), None);
         assert_eq!(map.shortest_distance_to_goal(), Some(2));
         assert_eq!(map.longest_distance_from_goal(), Some(4));
     }
+
+    #[test]
+    fn test_path_to_goal() {
+        let map = example_map();
+        let path = map.path_to_goal().unwrap();
+        assert_eq!(path.len(), map.shortest_distance_to_goal().unwrap());
+        let end = path.into_iter().fold(Position::default(), |pos, dir| pos + dir);
+        assert_eq!(Some(end), map.goal);
+    }
+
+    #[test]
+    fn test_oxygen_spread() {
+        let map = example_map();
+        let spread = map.oxygen_spread();
+        assert_eq!(spread.len() - 1, map.longest_distance_from_goal().unwrap());
+        assert_eq!(spread[0], HashSet::from([map.goal.unwrap()]));
+        assert_eq!(spread.iter().map(HashSet::len).sum::<usize>(), 8);
+    }
+
+    #[test]
+    fn test_render() {
+        let map = example_map();
+        assert_eq!(
+            map.render(Position::default()),
+            " ##   \n#..## \n#.#D.#\n#.G.# \n ###  "
+        );
+    }
 }
@@ -1,46 +1,174 @@
+use thiserror::Error;
+
+use crate::solver::{Answer, Solver};
+
+#[derive(Debug, Error)]
+enum FftError {
+    #[error("Window {start}..{end} is outside of the signal of length {len}")]
+    OutOfRange {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    #[error("The base pattern is empty")]
+    EmptyPattern,
+}
+
 #[aoc(day16, part1)]
 fn part_1(signal: &[u8]) -> String {
-    let mut signal = signal.to_vec();
-    flawed_frequency_transmission(&mut signal, 0, 100);
-    str::from_utf8(&signal[..8]).unwrap().to_string()
+    fft_digits(signal, 100, 0, 8).unwrap()
+}
+
+/// Digits `start..start + len` of the signal after `phases` phases.
+///
+/// When the window lies in the second half of the signal, only the suffix starting at `start`
+/// affects it, and every pattern there is zeros followed by ones, so the faster `run_phase2` is
+/// used. Otherwise the full patterns are evaluated.
+fn fft_digits(signal: &[u8], phases: usize, start: usize, len: usize) -> Result<String, FftError> {
+    let end = start + len;
+    if end > signal.len() {
+        return Err(FftError::OutOfRange {
+            start,
+            end,
+            len: signal.len(),
+        });
+    }
+    let digits = if start >= signal.len() / 2 {
+        let mut suffix = signal[start..].to_vec();
+        flawed_frequency_transmission2(&mut suffix, phases);
+        suffix.truncate(len);
+        suffix
+    } else {
+        let mut signal = signal.to_vec();
+        flawed_frequency_transmission(&mut signal, 0, phases);
+        signal[start..end].to_vec()
+    };
+    Ok(String::from_utf8(digits).unwrap())
 }
 
 #[aoc(day16, part2)]
 fn part_2(signal: &[u8]) -> String {
-    let n = signal.len();
     let start: usize = str::from_utf8(&signal[..7]).unwrap().parse().unwrap();
-    let end = n * 10_000;
+    repeated_suffix_digits(signal, 10_000, start, 100)
+}
+
+/// Eight digits from `start` of the signal repeated `repeats` times, after `phases` phases. The
+/// digits have to be in the second half of the repeated signal.
+///
+/// There, each phase replaces every digit with the sum of itself and the digits after it, so after
+/// `phases` phases the digit at `start + k` has been counted `C(k + phases - 1, phases - 1)` times.
+/// Those weights are computed one at a time, and the digits are read straight from `signal`,
+/// without expanding the repeated signal.
+fn repeated_suffix_digits(signal: &[u8], repeats: usize, start: usize, phases: usize) -> String {
+    let n = signal.len();
+    let end = n * repeats;
+    let mut sums = [0; 8];
+    for k in 0..end - start {
+        let weight = if phases == 0 {
+            u32::from(k == 0)
+        } else {
+            binomial_mod_10(k + phases - 1, phases - 1)
+        };
+        if weight == 0 {
+            continue;
+        }
+        for (ix, sum) in (start + k..end).zip(&mut sums) {
+            *sum = (*sum + weight * u32::from(signal[ix % n] - b'0')) % 10;
+        }
+    }
+    sums.iter()
+        .map(|&sum| char::from_digit(sum, 10).unwrap())
+        .collect()
+}
+
+/// Like `repeated_suffix_digits`, but expands the repeated signal and runs every phase on it.
+#[allow(unused, reason = "tests")]
+fn repeated_suffix_digits_materialized(
+    signal: &[u8],
+    repeats: usize,
+    start: usize,
+    phases: usize,
+) -> String {
+    let n = signal.len();
+    let end = n * repeats;
 
     let mut real_signal = Vec::with_capacity(end - start);
     real_signal.extend_from_slice(&signal[start % n..]);
-    for _ in (start.div_ceil(n) * n..end).step_by(n) {
+    for _ in ((start / n + 1) * n..end).step_by(n) {
         real_signal.extend_from_slice(signal);
     }
     assert_eq!(real_signal.len(), end - start);
-    
-    flawed_frequency_transmission2(&mut real_signal, 100);
+
+    flawed_frequency_transmission2(&mut real_signal, phases);
 
     str::from_utf8(&real_signal[..8]).unwrap().to_string()
 }
 
+/// `C(n, k) mod 10`, combined from the results modulo 2 and 5.
+fn binomial_mod_10(n: usize, k: usize) -> u32 {
+    (5 * binomial_mod_prime(n, k, 2) + 6 * binomial_mod_prime(n, k, 5)) % 10
+}
+
+/// `C(n, k) mod p` by Lucas' theorem: the product of the binomials of the base `p` digits.
+fn binomial_mod_prime(mut n: usize, mut k: usize, p: usize) -> u32 {
+    let mut result = 1;
+    while k > 0 {
+        let (n_digit, k_digit) = (n % p, k % p);
+        if k_digit > n_digit {
+            return 0;
+        }
+        let binomial = (0..k_digit).fold(1, |acc, i| acc * (n_digit - i) / (i + 1));
+        result = result * binomial % p;
+        n /= p;
+        k /= p;
+    }
+    u32::try_from(result).unwrap()
+}
+
+pub struct Day16;
+
+impl Solver for Day16 {
+    fn part1(&self, input: &str) -> String {
+        part_1(input.as_bytes()).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        part_2(input.as_bytes()).into_answer()
+    }
+}
+
 fn flawed_frequency_transmission(signal: &mut [u8], offset: usize, times: usize) {
     for _ in 0..times {
         run_phase(signal, offset);
     }
 }
 
+const BASE_PATTERN: [i8; 4] = [1, 0, -1, 0];
+
 fn run_phase(signal: &mut [u8], offset: usize) {
+    run_phase_with(signal, offset, &BASE_PATTERN).expect("the base pattern is not empty");
+}
+
+/// Runs a phase with a different base pattern. For each output, the pattern is zero up to the
+/// output position, and then cycles through `pattern` with every element repeated once per
+/// position of the output. Fails if `pattern` is empty.
+fn run_phase_with(signal: &mut [u8], offset: usize, pattern: &[i8]) -> Result<(), FftError> {
+    if pattern.is_empty() {
+        return Err(FftError::EmptyPattern);
+    }
     for output_ix in 0..signal.len() {
         let sum = signal
             .iter()
             .enumerate()
             .map(|(pattern_ix, &ch)| {
-                (ch - b'0').cast_signed() * get_pattern(offset + output_ix, offset + pattern_ix)
+                (ch - b'0').cast_signed()
+                    * get_pattern_with(offset + output_ix, offset + pattern_ix, pattern)
             })
             .map(i32::from)
             .sum::<i32>();
         signal[output_ix] = (sum.unsigned_abs() % 10) as u8 + b'0';
     }
+    Ok(())
 }
 
 fn flawed_frequency_transmission2(signal: &mut [u8], times: usize) {
@@ -58,11 +186,16 @@ fn run_phase2(signal: &mut [u8]) {
     }
 }
 
+#[allow(unused, reason = "tests")]
 const fn get_pattern(out_position: usize, pattern_position: usize) -> i8 {
+    get_pattern_with(out_position, pattern_position, &BASE_PATTERN)
+}
+
+const fn get_pattern_with(out_position: usize, pattern_position: usize, pattern: &[i8]) -> i8 {
     if pattern_position < out_position {
         0
     } else {
-        [1, 0, -1, 0][((pattern_position - out_position) / (out_position + 1)) % 4]
+        pattern[((pattern_position - out_position) / (out_position + 1)) % pattern.len()]
     }
 }
 
@@ -90,6 +223,24 @@ mod tests {
         input
     }
 
+    #[test_case(&[1, 0, -1, 0], *b"12345678" => *b"48226158")]
+    #[test_case(&[1], *b"12345678" => *b"65306158")]
+    #[test_case(&[0, 1], *b"12345678" => *b"07180000")]
+    fn test_run_phase_with<const N: usize>(pattern: &[i8], mut input: [u8; N]) -> [u8; N] {
+        run_phase_with(&mut input, 0, pattern).unwrap();
+        input
+    }
+
+    #[test]
+    fn test_run_phase_with_empty_pattern() {
+        let mut input = *b"12345678";
+        assert!(matches!(
+            run_phase_with(&mut input, 0, &[]),
+            Err(FftError::EmptyPattern)
+        ));
+        assert_eq!(&input, b"12345678");
+    }
+
     // Second half will be correct using run_phase2
     #[test_case(*b"12345678" => *b"6158")]
     #[test_case(*b"48226158" => *b"0438")]
@@ -108,10 +259,64 @@ mod tests {
         str::from_utf8(&signal[..8]).unwrap().to_string()
     }
 
+    #[test_case(b"12345678", 4, 0, 8 => "01029498")]
+    #[test_case(b"12345678", 4, 2, 3 => "029")]
+    #[test_case(b"12345678", 4, 4, 4 => "9498")]
+    fn test_fft_digits(signal: &[u8], phases: usize, start: usize, len: usize) -> String {
+        fft_digits(signal, phases, start, len).unwrap()
+    }
+
+    #[test]
+    fn test_fft_digits_second_half() {
+        let signal = b"80871224585914546619083218645595";
+        let mut full = signal.to_vec();
+        flawed_frequency_transmission(&mut full, 0, 100);
+        for start in 16..24 {
+            let expected = str::from_utf8(&full[start..start + 8]).unwrap();
+            assert_eq!(fft_digits(signal, 100, start, 8).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_fft_digits_out_of_range() {
+        assert!(fft_digits(b"12345678", 1, 6, 4).is_err());
+    }
+
     #[test_case(b"03036732577212944063491565474664" => "84462026")]
     #[test_case(b"02935109699940807407585447034323" => "78725270")]
     #[test_case(b"03081770884921959731165446850517" => "53553731")]
     fn test_part_2(signal: &[u8]) -> String {
         part_2(signal)
     }
+
+    #[test_case(b"03036732577212944063491565474664", 10_000, 303_673)]
+    #[test_case(b"02935109699940807407585447034323", 10_000, 293_510)]
+    #[test_case(b"12345678", 3, 12)]
+    #[test_case(b"12345678", 3, 16)]
+    fn test_repeated_suffix_digits(signal: &[u8], repeats: usize, start: usize) {
+        for phases in [0, 1, 2, 7, 100] {
+            assert_eq!(
+                repeated_suffix_digits(signal, repeats, start, phases),
+                repeated_suffix_digits_materialized(signal, repeats, start, phases),
+                "{phases} phases"
+            );
+        }
+    }
+
+    #[test]
+    fn test_binomial_mod_10() {
+        let mut row = vec![1_u64];
+        for n in 0..60 {
+            for (k, &binomial) in row.iter().enumerate() {
+                let expected = binomial % 10;
+                assert_eq!(u64::from(binomial_mod_10(n, k)), expected, "C({n}, {k})");
+            }
+            row = [&[0][..], &row]
+                .concat()
+                .iter()
+                .zip(row.iter().chain([&0]))
+                .map(|(a, b)| a + b)
+                .collect();
+        }
+    }
 }
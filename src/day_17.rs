@@ -2,7 +2,16 @@ use std::fmt::{Display, Write};
 use std::num::ParseIntError;
 use std::ops::{Add, AddAssign, Index};
 
-use crate::machine::{parse_program, Machine, MachineError, Value};
+use thiserror::Error;
+
+use crate::machine::{parse_program, HaltReason, Machine, MachineError, Value};
+use crate::solver::{Answer, Solver};
+
+#[derive(Debug, Error)]
+enum SubdivError {
+    #[error("Path can not be split into three movement functions that fit in memory")]
+    NoSubdivision,
+}
 
 #[aoc_generator(day17)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
@@ -11,15 +20,21 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day17, part1)]
 fn part_1(program: &[Value]) -> usize {
-    let mut machine = Machine::new(program);
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> usize {
+    let mut machine = Machine::new(program).with_log(trace);
     let map = read_map(&mut machine).unwrap();
     sum_alignment_parameters(&map)
 }
 
 fn read_map(machine: &mut Machine) -> Result<Map<u8>, MachineError> {
-    match machine.run_until_stopped() {
-        Ok(()) | Err(MachineError::EmptyInput) => {}
-        err => err?,
+    // The map is followed either by the end of the program or by a prompt for input
+    if let Err(err) = machine.run_until_stopped()
+        && machine.halt_reason() != Some(HaltReason::BlockedOnInput)
+    {
+        return Err(err);
     }
     let mut output = Vec::new();
     let mut line_len = 0;
@@ -103,23 +118,72 @@ fn sum_alignment_parameters(map: &Map<u8>) -> usize {
 }
 
 #[aoc(day17, part2)]
-fn part_2(program: &[Value]) -> Value {
-    let mut machine = Machine::new(program);
+fn part_2(program: &[Value]) -> Result<Value, SubdivError> {
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> Result<Value, SubdivError> {
+    Ok(solve_part2(program, trace)?.dust)
+}
+
+#[derive(Default)]
+pub struct Day17 {
+    trace: bool,
+}
+
+impl Solver for Day17 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
+/// Amount of dust collected, along with the movement program that was sent to the robot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CleaningReport {
+    dust: Value,
+    main: String,
+    subroutines: [String; Subroutine::all().len()],
+}
+
+fn solve_part2(program: &[Value], trace: bool) -> Result<CleaningReport, SubdivError> {
+    let mut machine = Machine::new(program).with_log(trace);
     machine.write(0, 2);
 
     let map = read_map(&mut machine).unwrap();
 
     let path = collect_path(&map);
 
-    let subdiv = PathSubdivision::subdivide_path(&path).unwrap();
-    let mut program_text = subdiv.to_string();
-    program_text.push_str("n\n");
+    let subdiv = PathSubdivision::subdivide_path(&path)?;
+    let program_text = movement_program(&subdiv, false);
 
-    machine.inputs.extend(program_text.bytes().map(Value::from));
+    machine.extend_ascii(&program_text);
 
     machine.run_until_stopped().unwrap();
 
-    machine.outputs.pop_back().unwrap()
+    Ok(CleaningReport {
+        dust: machine.outputs.pop_back().unwrap(),
+        main: subdiv.main_text(),
+        subroutines: Subroutine::all().map(|sub| subdiv.subroutine_text(sub)),
+    })
+}
+
+/// Movement routines followed by the answer to the continuous video feed prompt.
+fn movement_program(subdiv: &PathSubdivision, continuous_video: bool) -> String {
+    let mut program_text = subdiv.to_string();
+    program_text.push_str(if continuous_video { "y\n" } else { "n\n" });
+    program_text
 }
 
 fn collect_path(map: &Map<u8>) -> Vec<Action> {
@@ -135,6 +199,7 @@ fn collect_path(map: &Map<u8>) -> Vec<Action> {
     let mut path = Vec::new();
     loop {
         let mut forward_count = 0;
+        // Always go straight through intersections, and only turn once the scaffold ends
         while is_open(map[pos + dir]) {
             pos += dir;
             forward_count += 1;
@@ -185,20 +250,29 @@ struct PathSubdivision {
     subroutines: [Vec<Action>; Subroutine::all().len()],
 }
 
+/// Maximum number of characters in each line of the movement program.
+const MAX_LINE_LEN: usize = 20;
+
 impl PathSubdivision {
+    /// Backtracking search for subroutines covering the rest of the path. Candidates that no
+    /// longer fit in memory are pruned as soon as they are formed.
     fn walk(&mut self, path: &[Action]) -> bool {
         if path.is_empty() {
-            return self.main.len() * 2 - 1 <= 20
-                && self
-                    .subroutines
-                    .iter()
-                    .all(|s| s.iter().map(|a| a.len() + 1).sum::<usize>() - 1 <= 20);
+            return true;
+        }
+        if self.main.len() * 2 + 1 > MAX_LINE_LEN {
+            return false;
         }
         for sub in Subroutine::all() {
             let sub_ix = sub as usize;
             if self.subroutines[sub_ix].is_empty() {
                 self.main.push(sub);
+                let mut line_len = 0;
                 for (path_ix, &action) in path.iter().enumerate() {
+                    line_len += action.len() + usize::from(path_ix > 0);
+                    if line_len > MAX_LINE_LEN {
+                        break;
+                    }
                     self.subroutines[sub_ix].push(action);
                     if self.walk(&path[path_ix + 1..]) {
                         return true;
@@ -206,6 +280,7 @@ impl PathSubdivision {
                 }
                 self.subroutines[sub_ix].clear();
                 self.main.pop();
+                // The remaining unused subroutines would only repeat the same search
                 return false;
             }
             if path.starts_with(&self.subroutines[sub_ix]) {
@@ -219,34 +294,45 @@ impl PathSubdivision {
         false
     }
 
-    fn subdivide_path(path: &[Action]) -> Option<Self> {
+    fn main_text(&self) -> String {
+        join_commas(&self.main)
+    }
+
+    fn subroutine_text(&self, sub: Subroutine) -> String {
+        join_commas(&self.subroutines[sub as usize])
+    }
+
+    fn subdivide_path(path: &[Action]) -> Result<Self, SubdivError> {
         let mut subdiv = Self::default();
-        subdiv.walk(path).then_some(subdiv)
+        if subdiv.walk(path) {
+            Ok(subdiv)
+        } else {
+            Err(SubdivError::NoSubdivision)
+        }
     }
 }
 
 impl Display for PathSubdivision {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for (i, sub) in self.main.iter().enumerate() {
-            if i > 0 {
-                f.write_char(',')?;
-            }
-            write!(f, "{sub}")?;
-        }
-        writeln!(f)?;
-        for sub in &self.subroutines {
-            for (i, action) in sub.iter().enumerate() {
-                if i > 0 {
-                    f.write_char(',')?;
-                }
-                write!(f, "{action}")?;
-            }
-            writeln!(f)?;
+        writeln!(f, "{}", self.main_text())?;
+        for sub in Subroutine::all() {
+            writeln!(f, "{}", self.subroutine_text(sub))?;
         }
         Ok(())
     }
 }
 
+fn join_commas(items: &[impl Display]) -> String {
+    let mut text = String::new();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            text.push(',');
+        }
+        write!(text, "{item}").unwrap();
+    }
+    text
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Position {
     x: Value,
@@ -403,6 +489,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_path_through_intersection() {
+        let map = "\
+            ....#....\n\
+            ....#....\n\
+            ^########\n\
+            ....#...#\n\
+            ....#####\
+        ";
+        let map = Map::new(map.as_bytes().to_vec(), |&ch| ch == b'\n', b' ');
+        let path = collect_path(&map);
+        assert_eq!(path, parse_actions("R,8,R,2,R,4,R,4"));
+    }
+
     #[test]
     fn test_subdivide() {
         let map = Map::new(EXAMPLE2.as_bytes().to_vec(), |&ch| ch == b'\n', b' ');
@@ -418,4 +518,63 @@ mod tests {
         }
         assert_eq!(path, reconstucted);
     }
+
+    fn parse_actions(text: &str) -> Vec<Action> {
+        text.split(',')
+            .map(|part| match part {
+                "L" => Action::Left,
+                "R" => Action::Right,
+                _ => Action::Forward(part.parse().unwrap()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_subdivide_backtracking() {
+        // The shortest routines that cover the path take far too many calls, so longer routines
+        // have to be tried before the path is split
+        let path = parse_actions(&["L,1"; 12].join(","));
+        let subdiv = PathSubdivision::subdivide_path(&path).unwrap();
+        let text = subdiv.to_string();
+        for line in text.lines() {
+            assert!(line.len() <= MAX_LINE_LEN, "len <= 20: {line:?}");
+        }
+        let reconstucted = subdiv
+            .main
+            .iter()
+            .flat_map(|&sub| subdiv.subroutines[sub as usize].iter().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(path, reconstucted);
+    }
+
+    #[test]
+    fn test_subdivide_impossible() {
+        let path = (1..=20)
+            .flat_map(|n| [Action::Right, Action::Forward(n)])
+            .collect::<Vec<_>>();
+        assert!(PathSubdivision::subdivide_path(&path).is_err());
+    }
+
+    #[test]
+    fn test_routine_text() {
+        let path = parse_actions("R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2");
+        let subdiv = PathSubdivision::subdivide_path(&path).unwrap();
+        let lines = [
+            subdiv.main_text(),
+            subdiv.subroutine_text(Subroutine::A),
+            subdiv.subroutine_text(Subroutine::B),
+            subdiv.subroutine_text(Subroutine::C),
+        ];
+        assert_eq!(subdiv.to_string(), lines.join("\n") + "\n");
+        assert!(lines[0].starts_with("A,"));
+    }
+
+    #[test]
+    fn test_movement_program() {
+        let map = Map::new(EXAMPLE2.as_bytes().to_vec(), |&ch| ch == b'\n', b' ');
+        let path = collect_path(&map);
+        let subdiv = PathSubdivision::subdivide_path(&path).unwrap();
+        assert_eq!(movement_program(&subdiv, true).lines().last(), Some("y"));
+        assert_eq!(movement_program(&subdiv, false).lines().last(), Some("n"));
+    }
 }
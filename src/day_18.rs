@@ -5,12 +5,22 @@ use std::ops::{Add, AddAssign, Index, IndexMut};
 
 use thiserror::Error;
 
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
 enum ParseError {
     #[error("Invalid tile: {0:?}")]
     InvalidTile(char),
 }
 
+#[derive(Debug, Error, PartialEq, Eq)]
+enum KeyError {
+    #[error("Keys that can not be reached from any entrance: {}", String::from_utf8_lossy(.0))]
+    Unreachable(Vec<u8>),
+    #[error("Every order of collecting the keys is stuck behind a door")]
+    NoSolution,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum Location {
     Entrance(u8),
@@ -105,6 +115,22 @@ impl<T> IndexMut<Position> for Map<T> {
     }
 }
 
+#[cfg(feature = "rgba")]
+impl<T: Clone> crate::render::Raster for Map<T> {
+    type Cell = T;
+
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn cells(&self) -> impl Iterator<Item = T> {
+        (0..self.height).flat_map(move |y| {
+            let row = y * self.stride;
+            self.data[row..row + self.width].iter().cloned()
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Position {
     x: Value,
@@ -168,14 +194,16 @@ fn parse(input: &str) -> Result<Map<Tile>, ParseError> {
 }
 
 #[aoc(day18, part1)]
-fn part_1(map: &Map<Tile>) -> usize {
+fn part_1(map: &Map<Tile>) -> Result<usize, KeyError> {
     let (locations, positions) = locations_ans_positions(map);
+    check_reachable(map, &locations, &positions)?;
     let neighbors = find_all_neighbors(map, &positions);
-    find_all_keys(Location::Entrance(0), &locations, &neighbors).unwrap()
+    let edges = build_edges(&locations, &neighbors);
+    find_all_keys(Location::Entrance(0), &locations, &edges).ok_or(KeyError::NoSolution)
 }
 
 #[aoc(day18, part2)]
-fn part_2(map: &Map<Tile>) -> usize {
+fn part_2(map: &Map<Tile>) -> Result<usize, KeyError> {
     let (mut locations, mut positions) = locations_ans_positions(map);
     // All but one of the examples have the entrances already expanded, so check before trying.
     let modified_map = if locations
@@ -187,9 +215,11 @@ fn part_2(map: &Map<Tile>) -> usize {
         &expand_entrance(map, &mut locations, &mut positions)
     };
 
+    check_reachable(modified_map, &locations, &positions)?;
     let neighbors = find_all_neighbors(modified_map, &positions);
+    let edges = build_edges(&locations, &neighbors);
 
-    find_all_keys_parallel(
+    find_all_keys_partitioned(
         [
             Location::Entrance(0),
             Location::Entrance(1),
@@ -197,9 +227,21 @@ fn part_2(map: &Map<Tile>) -> usize {
             Location::Entrance(3),
         ],
         &locations,
-        &neighbors,
+        &edges,
     )
-    .unwrap()
+    .ok_or(KeyError::NoSolution)
+}
+
+pub struct Day18;
+
+impl Solver for Day18 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
 }
 
 fn expand_entrance(
@@ -250,7 +292,7 @@ fn locations_ans_positions(map: &Map<Tile>) -> (Vec<Location>, Vec<Position>) {
         .unzip()
 }
 
-fn find_all_neighbors(map: &Map<Tile>, positions: &[Position]) -> Vec<Vec<(Location, usize)>> {
+fn find_all_neighbors(map: &Map<Tile>, positions: &[Position]) -> Vec<Vec<(Location, usize, u32)>> {
     let mut neighbors = vec![vec![]; positions.len()];
     for (index, &pos) in positions.iter().enumerate() {
         find_neighbors(map, pos, &mut neighbors[index]);
@@ -258,83 +300,222 @@ fn find_all_neighbors(map: &Map<Tile>, positions: &[Position]) -> Vec<Vec<(Locat
     neighbors
 }
 
-fn find_neighbors(map: &Map<Tile>, start: Position, neighbors: &mut Vec<(Location, usize)>) {
+/// Finds every key reachable from `start`, along with its distance and a mask of the doors along
+/// the way. Other keys along the way are walked over. A key can be listed more than once, if a
+/// longer path to it needs fewer doors.
+fn find_neighbors(map: &Map<Tile>, start: Position, neighbors: &mut Vec<(Location, usize, u32)>) {
     let mut pending = VecDeque::new();
-    pending.push_back((start, 0));
-    let mut visited = HashSet::new();
-    while let Some((pos, dist)) = pending.pop_front() {
-        if !visited.insert(pos) {
+    pending.push_back((start, 0, 0_u32));
+    // Door masks each position has been reached with. Since the search is breadth first, any
+    // later path that needs a superset of those doors is never better.
+    let mut visited = HashMap::<Position, Vec<u32>>::new();
+    while let Some((pos, dist, mut doors)) = pending.pop_front() {
+        let masks = visited.entry(pos).or_default();
+        if masks.iter().any(|&mask| mask & !doors == 0) {
             continue;
         }
-        if pos != start
-            && let Tile::Location(loc) = map[pos]
-        {
-            neighbors.push((loc, dist));
-            continue;
+        masks.push(doors);
+        if pos != start {
+            match map[pos] {
+                Tile::Location(loc @ Location::Key(_)) => neighbors.push((loc, dist, doors)),
+                Tile::Location(Location::Door(key)) => doors |= 1 << key,
+                _ => {}
+            }
         }
         for dir in Direction::all() {
             let next = pos + dir;
-            if matches!(map[next], Tile::Wall | Tile::Void) || visited.contains(&next) {
+            if matches!(map[next], Tile::Wall | Tile::Void) {
                 continue;
             }
-            pending.push_back((next, dist + 1));
+            pending.push_back((next, dist + 1, doors));
+        }
+    }
+}
+
+/// Edge to the location with the given index, with its distance and a mask of the keys needed to
+/// pass through it.
+type Edge = (usize, usize, u32);
+
+/// Replaces the neighboring keys by their indices.
+fn build_edges(
+    locations: &[Location],
+    neighbors: &[Vec<(Location, usize, u32)>],
+) -> Vec<Vec<Edge>> {
+    let indices = locations
+        .iter()
+        .enumerate()
+        .map(|(index, &loc)| (loc, index))
+        .collect::<HashMap<_, _>>();
+    neighbors
+        .iter()
+        .map(|list| {
+            list.iter()
+                .map(|&(loc, dist, doors)| (indices[&loc], dist, doors))
+                .collect()
+        })
+        .collect()
+}
+
+/// Fails with the letters of every key that no entrance has a path to, even with every door open.
+fn check_reachable(
+    map: &Map<Tile>,
+    locations: &[Location],
+    positions: &[Position],
+) -> Result<(), KeyError> {
+    let mut pending = locations
+        .iter()
+        .zip(positions)
+        .filter(|(loc, _)| matches!(loc, Location::Entrance(_)))
+        .map(|(_, &pos)| pos)
+        .collect::<VecDeque<_>>();
+    let mut visited = HashSet::new();
+    while let Some(pos) = pending.pop_front() {
+        if !visited.insert(pos) {
+            continue;
+        }
+        for dir in Direction::all() {
+            let next = pos + dir;
+            if !matches!(map[next], Tile::Wall | Tile::Void) && !visited.contains(&next) {
+                pending.push_back(next);
+            }
         }
     }
+    let mut unreachable = locations
+        .iter()
+        .zip(positions)
+        .filter_map(|(&loc, pos)| match loc {
+            Location::Key(k) if !visited.contains(pos) => Some(b'a' + k),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if unreachable.is_empty() {
+        return Ok(());
+    }
+    unreachable.sort_unstable();
+    Err(KeyError::Unreachable(unreachable))
+}
+
+fn find_all_keys(start: Location, locations: &[Location], edges: &[Vec<Edge>]) -> Option<usize> {
+    find_key_order(start, locations, edges).map(|(dist, _)| dist)
 }
 
-fn find_all_keys(
+/// Shortest distance to collect all keys, along with the letters of the keys in the order they
+/// were collected.
+fn find_key_order(
     start: Location,
     locations: &[Location],
-    neighbors: &[Vec<(Location, usize)>],
-) -> Option<usize> {
+    edges: &[Vec<Edge>],
+) -> Option<(usize, Vec<u8>)> {
     let all_keys_mask = locations
         .iter()
         .map(|l| if let &Location::Key(k) = l { 1 << k } else { 0 })
         .sum();
+    find_keys(start, all_keys_mask, locations, edges)
+}
+
+/// Shortest distance to collect the keys in `all_keys_mask`, along with the order they were
+/// collected in.
+fn find_keys(
+    start: Location,
+    all_keys_mask: u32,
+    locations: &[Location],
+    edges: &[Vec<Edge>],
+) -> Option<(usize, Vec<u8>)> {
+    type State = (usize, u32);
     let start_index = locations.iter().position(|&l| l == start).unwrap();
-    let mut visited = HashMap::<(usize, u32), usize>::new();
+    let mut visited = HashMap::<State, (usize, Option<State>)>::new();
     let mut pending = BinaryHeap::new();
-    pending.push((Reverse(0), start_index, 0_u32));
-    while let Some((Reverse(dist), index, mut keys)) = pending.pop() {
+    pending.push((Reverse(0), start_index, 0_u32, None));
+    while let Some((Reverse(dist), index, mut keys, prev)) = pending.pop() {
         match visited.entry((index, keys)) {
-            Entry::Occupied(o) if *o.get() <= dist => {
+            Entry::Occupied(o) if o.get().0 <= dist => {
                 continue;
             }
             Entry::Occupied(mut o) => {
-                o.insert(dist);
+                o.insert((dist, prev));
             }
             Entry::Vacant(v) => {
-                v.insert(dist);
+                v.insert((dist, prev));
             }
         }
+        let state = (index, keys);
         if let Location::Key(key) = locations[index] {
             keys |= 1 << key;
         }
         if keys == all_keys_mask {
-            return Some(dist);
+            let mut order = Vec::new();
+            let mut current = Some(state);
+            while let Some((index, keys)) = current {
+                if let Location::Key(key) = locations[index]
+                    && keys & (1 << key) == 0
+                {
+                    order.push(b'a' + key);
+                }
+                current = visited[&(index, keys)].1;
+            }
+            order.reverse();
+            return Some((dist, order));
         }
-        for &(next, delta) in &neighbors[index] {
-            if let Location::Door(key) = next
-                && (keys & (1 << key)) == 0
-            {
+        for &(next_ix, delta, required) in &edges[index] {
+            if required & !keys != 0 {
                 continue;
             }
-            let next_ix = locations.iter().position(|&l| l == next).unwrap();
-            if let Some(&prev_dist) = visited.get(&(next_ix, keys))
+            if let Some(&(prev_dist, _)) = visited.get(&(next_ix, keys))
                 && dist + delta >= prev_dist
             {
                 continue;
             }
-            pending.push((Reverse(dist + delta), next_ix, keys));
+            pending.push((Reverse(dist + delta), next_ix, keys, Some(state)));
         }
     }
     None
 }
 
+/// Solves each robot's part of the vault on its own, if none of its doors are opened by keys
+/// that another robot has to collect. Otherwise falls back to `find_all_keys_parallel`.
+fn find_all_keys_partitioned(
+    starts: [Location; 4],
+    locations: &[Location],
+    edges: &[Vec<Edge>],
+) -> Option<usize> {
+    let key_mask = |index: usize| {
+        if let Location::Key(k) = locations[index] {
+            1 << k
+        } else {
+            0
+        }
+    };
+    let start_indices = starts.map(|start| locations.iter().position(|&l| l == start).unwrap());
+    // Every key a robot can reach, ignoring doors
+    let quadrants = start_indices.map(|start| {
+        edges[start]
+            .iter()
+            .map(|&(index, _, _)| key_mask(index))
+            .fold(0_u32, |acc, mask| acc | mask)
+    });
+    let independent = start_indices
+        .into_iter()
+        .zip(quadrants)
+        .all(|(start, quadrant)| {
+            std::iter::once(start)
+                .chain((0..locations.len()).filter(|&index| key_mask(index) & quadrant != 0))
+                .flat_map(|index| &edges[index])
+                .all(|&(_, _, required)| required & !quadrant == 0)
+        });
+    if !independent {
+        return find_all_keys_parallel(starts, locations, edges);
+    }
+    starts
+        .into_iter()
+        .zip(quadrants)
+        .map(|(start, quadrant)| Some(find_keys(start, quadrant, locations, edges)?.0))
+        .sum()
+}
+
 fn find_all_keys_parallel(
     starts: [Location; 4],
     locations: &[Location],
-    neighbors: &[Vec<(Location, usize)>],
+    edges: &[Vec<Edge>],
 ) -> Option<usize> {
     let all_keys_mask = locations
         .iter()
@@ -365,13 +546,10 @@ fn find_all_keys_parallel(
             return Some(dist);
         }
         for (ix, index) in indices.into_iter().enumerate() {
-            for &(next, delta) in &neighbors[index] {
-                if let Location::Door(key) = next
-                    && (keys & (1 << key)) == 0
-                {
+            for &(next_ix, delta, required) in &edges[index] {
+                if required & !keys != 0 {
                     continue;
                 }
-                let next_ix = locations.iter().position(|&l| l == next).unwrap();
                 let mut new_indices = indices;
                 new_indices[ix] = next_ix;
                 if let Some(&prev_dist) = visited.get(&(new_indices, keys))
@@ -486,16 +664,102 @@ mod tests {
         #############\
     ";
 
+    #[cfg(feature = "rgba")]
+    #[test]
+    fn test_to_rgba() {
+        let map = parse(EXAMPLE1).unwrap();
+        let (pixels, width, height) = crate::render::to_rgba(&map, |&tile| match tile {
+            Tile::Wall => [0, 0, 0, 255],
+            _ => [255, 255, 255, 255],
+        });
+        assert_eq!((width, height), (9, 3));
+        assert_eq!(pixels.len(), 9 * 3 * 4);
+        assert_eq!(pixels[(9 + 1) * 4..(9 + 2) * 4], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_build_edges() {
+        let map = parse(EXAMPLE1).unwrap();
+        let (locations, positions) = locations_ans_positions(&map);
+        let neighbors = find_all_neighbors(&map, &positions);
+        let edges = build_edges(&locations, &neighbors);
+        // b, A, @, a
+        assert_eq!(
+            locations,
+            [
+                Location::Key(1),
+                Location::Door(0),
+                Location::Entrance(0),
+                Location::Key(0)
+            ]
+        );
+        assert_eq!(edges[0], [(3, 6, 1)]);
+        assert_eq!(edges[2], [(3, 2, 0), (0, 4, 1)]);
+    }
+
+    /// The short way to `b` goes through door `A`, but there is a longer way around it.
+    const LOOP: &str = "\
+        ############################\n\
+        #b.A.@....................a#\n\
+        #.###.######################\n\
+        #.....######################\n\
+        ############################\
+    ";
+
     #[test_case(EXAMPLE1 => 8)]
     #[test_case(EXAMPLE2 => 86)]
     #[test_case(EXAMPLE3 => 132)]
     #[test_case(EXAMPLE4 => 136)]
     #[test_case(EXAMPLE5 => 81)]
+    #[test_case(LOOP => 37; "loop around a door")]
     fn test_part_1(input: &str) -> usize {
+        let map = parse(input).unwrap();
+        part_1(&map).unwrap()
+    }
+
+    #[test_case("#######\n#@.a#b#\n#######" => Err(KeyError::Unreachable(b"b".to_vec())); "walled off")]
+    #[test_case("#########\n#@aB.c#d#\n#########" => Err(KeyError::Unreachable(b"d".to_vec())); "behind a door")]
+    #[test_case("#########\n#b.A@B.a#\n#########" => Err(KeyError::NoSolution); "doors locked both ways")]
+    fn test_part_1_errors(input: &str) -> Result<usize, KeyError> {
         let map = parse(input).unwrap();
         part_1(&map)
     }
 
+    #[test_case(EXAMPLE1 => (8, b"ab".to_vec()))]
+    #[test_case(EXAMPLE2 => (86, b"abcdef".to_vec()))]
+    #[test_case(EXAMPLE3 => (132, b"bacdfeg".to_vec()))]
+    fn test_key_order(input: &str) -> (usize, Vec<u8>) {
+        let map = parse(input).unwrap();
+        let (locations, positions) = locations_ans_positions(&map);
+        let neighbors = find_all_neighbors(&map, &positions);
+        let edges = build_edges(&locations, &neighbors);
+        find_key_order(Location::Entrance(0), &locations, &edges).unwrap()
+    }
+
+    const INDEPENDENT: &str = "\
+        #########\n\
+        #bAa#.cC#\n\
+        ###@#@###\n\
+        #########\n\
+        ###@#@###\n\
+        #d..#..e#\n\
+        #########\
+    ";
+
+    #[test]
+    fn test_independent_quadrants() {
+        let map = parse(INDEPENDENT).unwrap();
+        let (locations, positions) = locations_ans_positions(&map);
+        let neighbors = find_all_neighbors(&map, &positions);
+        let edges = build_edges(&locations, &neighbors);
+        let starts = [0, 1, 2, 3].map(Location::Entrance);
+        assert_eq!(
+            find_all_keys_partitioned(starts, &locations, &edges),
+            Some(11)
+        );
+        assert_eq!(find_all_keys_parallel(starts, &locations, &edges), Some(11));
+    }
+
     #[test_case(EXAMPLE6 => 8)]
     #[test_case(EXAMPLE6_ALT => 8)]
     #[test_case(EXAMPLE7 => 24)]
@@ -503,6 +767,19 @@ mod tests {
     #[test_case(EXAMPLE9 => 72)]
     fn test_part_2(input: &str) -> usize {
         let map = parse(input).unwrap();
-        part_2(&map)
+        part_2(&map).unwrap()
+    }
+
+    #[test]
+    fn test_part_2_unreachable() {
+        let map = parse(
+            "#########\n\
+            #a.@#@.z#\n\
+            #########\n\
+            #..@#@#y#\n\
+            #########",
+        )
+        .unwrap();
+        assert_eq!(part_2(&map), Err(KeyError::Unreachable(b"y".to_vec())));
     }
 }
@@ -4,7 +4,9 @@ use std::ops::{Add, AddAssign};
 
 use thiserror::Error;
 
+use crate::geom::Position;
 use crate::machine::{parse_program, Machine, MachineError, Value};
+use crate::solver::{Answer, Solver};
 
 #[aoc_generator(day19)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
@@ -13,7 +15,12 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day19, part1)]
 fn part_1(program: &[Value]) -> usize {
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> usize {
     let mut controller = DroneController::new(program);
+    controller.machine.log = trace;
     let mut count = 0;
     for y in 0..50 {
         for x in 0..50 {
@@ -27,13 +34,41 @@ fn part_1(program: &[Value]) -> usize {
 }
 
 #[aoc(day19, part2)]
-fn part_2(program: &[Value]) -> i32 {
-    let pos = find_contained_box(program, 100).unwrap();
+fn part_2(program: &[Value]) -> i64 {
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> i64 {
+    let pos = find_contained_box(program, 100, trace).unwrap();
     pos.x * 10000 + pos.y
 }
 
-fn find_contained_box(program: &[Value], size: i32) -> Result<Position, RuntimeError> {
+#[derive(Default)]
+pub struct Day19 {
+    trace: bool,
+}
+
+impl Solver for Day19 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
+fn find_contained_box(program: &[Value], size: i64, trace: bool) -> Result<Position, RuntimeError> {
     let mut controller = DroneController::new(program);
+    controller.machine.log = trace;
     let mut corner = Position::new(50, 0);
     while controller.test_coordinates(corner)? == DroneResult::Stationary {
         corner += Direction::Down;
@@ -74,7 +109,7 @@ fn find_contained_box(program: &[Value], size: i32) -> Result<Position, RuntimeE
             if controller.test_coordinates(test)? == DroneResult::BeingPulled
                 && controller.test_coordinates(right)? == DroneResult::BeingPulled
                 && controller.test_coordinates(bottom)? == DroneResult::BeingPulled
-                && test.dist() < closest.dist() {
+                && test.magnitude() < closest.magnitude() {
                     closest = test;
                 } 
         }
@@ -106,8 +141,8 @@ impl<'a> DroneController<'a> {
 
         self.machine.reset(self.program);
 
-        self.machine.inputs.push_back(pos.x.into());
-        self.machine.inputs.push_back(pos.y.into());
+        self.machine.inputs.push_back(pos.x);
+        self.machine.inputs.push_back(pos.y);
         let res = self
             .machine
             .run_until_output()?
@@ -152,22 +187,6 @@ impl TryFrom<Value> for DroneResult {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-struct Position {
-    x: i32,
-    y: i32,
-}
-
-impl Position {
-    const fn new(x: i32, y: i32) -> Self {
-        Self { x, y }
-    }
-
-    const fn dist(self) -> u32 {
-        self.x.unsigned_abs() + self.y.unsigned_abs()
-    }
-}
-
 impl AddAssign<Direction> for Position {
     fn add_assign(&mut self, rhs: Direction) {
         match rhs {
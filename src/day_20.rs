@@ -1,6 +1,7 @@
 #![allow(unused)]
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fmt::{Display, Write};
 use std::mem::MaybeUninit;
 use std::ops::{Add, AddAssign, Index, IndexMut, RangeInclusive};
@@ -8,6 +9,8 @@ use std::str::FromStr;
 
 use thiserror::Error;
 
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[repr(C)]
 struct Grid<T> {
@@ -63,6 +66,19 @@ impl<T> IndexMut<Position> for Grid<T> {
     }
 }
 
+#[cfg(feature = "rgba")]
+impl<T: Clone> crate::render::Raster for Grid<T> {
+    type Cell = T;
+
+    fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    fn cells(&self) -> impl Iterator<Item = T> {
+        self.tiles.iter().cloned()
+    }
+}
+
 impl<T> Display for Grid<T>
 where
     T: Display,
@@ -80,7 +96,7 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Position {
     x: i32,
     y: i32,
@@ -178,6 +194,8 @@ impl Display for Tile {
 struct Measurement {
     full_width: usize,
     full_height: usize,
+    margin_x: usize,
+    margin_y: usize,
     grid_width: usize,
     grid_height: usize,
     hole_offset_x: usize,
@@ -195,44 +213,69 @@ enum MeasurePosition {
 }
 
 impl Measurement {
+    /// Measures the maze and its hole. The margins around the maze are detected from where the
+    /// walls and open tiles start, and lines may be missing their trailing spaces.
     fn measure(input: &str) -> Self {
+        let is_maze = |ch: u8| matches!(ch, b'#' | b'.');
         let full_height = input.lines().count();
-        let mut lines = input.lines();
-        let first_line = lines.next().unwrap();
-        let full_width = first_line.len();
-        let grid_width = full_width - 4;
-        let grid_height = full_height - 4;
+        let full_width = input.lines().map(str::len).max().unwrap();
+        let margin_y = input
+            .lines()
+            .position(|l| l.bytes().any(is_maze))
+            .unwrap();
+        let margin_bottom = input
+            .lines()
+            .rev()
+            .position(|l| l.bytes().any(is_maze))
+            .unwrap();
+        let margin_x = input
+            .lines()
+            .filter_map(|l| l.bytes().position(is_maze))
+            .min()
+            .unwrap();
+        let grid_width = input
+            .lines()
+            .filter_map(|l| l.bytes().rposition(is_maze))
+            .max()
+            .unwrap()
+            + 1
+            - margin_x;
+        let grid_height = full_height - margin_y - margin_bottom;
+        let inside = |l| slice_columns(l, margin_x, margin_x + grid_width);
 
         let hole_offset_y = input
             .lines()
-            .skip(2)
-            .position(|l| l[2..grid_width + 2].contains(' '))
+            .skip(margin_y)
+            .position(|l| inside(l).contains(' '))
             .unwrap()
-            + 2;
+            + margin_y;
         let hole_height = full_height
             - input
                 .lines()
                 .rev()
-                .skip(2)
-                .position(|l| l[2..grid_width + 2].contains(' '))
+                .skip(margin_bottom)
+                .position(|l| inside(l).contains(' '))
                 .unwrap()
-            - 2
+            - margin_bottom
             - hole_offset_y;
         let hole_first_line: &str = input.lines().nth(hole_offset_y).unwrap();
-        let hole_offset_x = hole_first_line[2..grid_width + 2]
+        let hole_offset_x = inside(hole_first_line)
             .bytes()
             .position(|ch| ch == b' ')
             .unwrap()
-            + 2;
-        let hole_width = hole_first_line[2..grid_width + 2]
+            + margin_x;
+        let hole_width = inside(hole_first_line)
             .bytes()
             .rposition(|ch| ch == b' ')
             .unwrap()
-            + 3
+            + 1
+            + margin_x
             - hole_offset_x;
         Self {
             full_width,
             full_height,
+            margin_x,
+            margin_y,
             grid_width,
             grid_height,
             hole_offset_x,
@@ -244,37 +287,37 @@ impl Measurement {
 
     fn locate(&self, row: usize, col: usize) -> MeasurePosition {
         let x_region: u8 = match col {
-            _ if col < 2 => 0,
+            _ if col < self.margin_x => 0,
             _ if col < self.hole_offset_x => 1,
             _ if col < self.hole_offset_x + 2 => 2,
             _ if col < self.hole_offset_x + self.hole_width - 2 => 3,
             _ if col < self.hole_offset_x + self.hole_width => 4,
-            _ if col < 2 + self.grid_width => 5,
+            _ if col < self.margin_x + self.grid_width => 5,
             _ => 6,
         };
         let y_region: u8 = match row {
-            _ if row < 2 => 0,
+            _ if row < self.margin_y => 0,
             _ if row < self.hole_offset_y => 1,
             _ if row < self.hole_offset_y + 2 => 2,
             _ if row < self.hole_offset_y + self.hole_height - 2 => 3,
             _ if row < self.hole_offset_y + self.hole_height => 4,
-            _ if row < 2 + self.grid_height => 5,
+            _ if row < self.margin_y + self.grid_height => 5,
             _ => 6,
         };
         match (x_region, y_region) {
             (0 | 6, 0 | 6) | (3, 3) => MeasurePosition::Void,
             (0, _) => MeasurePosition::GridBorder(
                 Direction::Left,
-                Position::new(0, i32::try_from(row - 2).unwrap()),
+                Position::new(0, i32::try_from(row - self.margin_y).unwrap()),
             ),
             (_, 0) => MeasurePosition::GridBorder(
                 Direction::Up,
-                Position::new(i32::try_from(col - 2).unwrap(), 0),
+                Position::new(i32::try_from(col - self.margin_x).unwrap(), 0),
             ),
             (_, 6) => MeasurePosition::GridBorder(
                 Direction::Down,
                 Position::new(
-                    i32::try_from(col - 2).unwrap(),
+                    i32::try_from(col - self.margin_x).unwrap(),
                     i32::try_from(self.grid_height - 1).unwrap(),
                 ),
             ),
@@ -282,41 +325,41 @@ impl Measurement {
                 Direction::Right,
                 Position::new(
                     i32::try_from(self.grid_width - 1).unwrap(),
-                    i32::try_from(row - 2).unwrap(),
+                    i32::try_from(row - self.margin_y).unwrap(),
                 ),
             ),
             (_, 1 | 5) | (1 | 5, _) => MeasurePosition::Grid(Position::new(
-                i32::try_from(col - 2).unwrap(),
-                i32::try_from(row - 2).unwrap(),
+                i32::try_from(col - self.margin_x).unwrap(),
+                i32::try_from(row - self.margin_y).unwrap(),
             )),
             // Unsymmetric: The hole corners are assigned to the sides
             (2, _) => MeasurePosition::HoleBorder(
                 Direction::Left,
                 Position::new(
-                    i32::try_from(self.hole_offset_x - 3).unwrap(),
-                    i32::try_from(row - 2).unwrap(),
+                    i32::try_from(self.hole_offset_x - self.margin_x - 1).unwrap(),
+                    i32::try_from(row - self.margin_y).unwrap(),
                 ),
             ),
             (3, 2) => MeasurePosition::HoleBorder(
                 Direction::Up,
                 Position::new(
-                    i32::try_from(col - 2).unwrap(),
-                    i32::try_from(self.hole_offset_y - 3).unwrap(),
+                    i32::try_from(col - self.margin_x).unwrap(),
+                    i32::try_from(self.hole_offset_y - self.margin_y - 1).unwrap(),
                 ),
             ),
             (3, 4) => MeasurePosition::HoleBorder(
                 Direction::Down,
                 Position::new(
-                    i32::try_from(col - 2).unwrap(),
-                    i32::try_from(self.hole_offset_y + self.hole_height - 2).unwrap(),
+                    i32::try_from(col - self.margin_x).unwrap(),
+                    i32::try_from(self.hole_offset_y + self.hole_height - self.margin_y).unwrap(),
                 ),
             ),
             // Unsymmetric: The hole corners are assigned to the sides
             (4, _) => MeasurePosition::HoleBorder(
                 Direction::Right,
                 Position::new(
-                    i32::try_from(self.hole_offset_x + self.hole_width - 2).unwrap(),
-                    i32::try_from(row - 2).unwrap(),
+                    i32::try_from(self.hole_offset_x + self.hole_width - self.margin_x).unwrap(),
+                    i32::try_from(row - self.margin_y).unwrap(),
                 ),
             ),
             _ => MeasurePosition::Void,
@@ -324,10 +367,16 @@ impl Measurement {
     }
 }
 
+/// Columns `start..end` of a line, or fewer if the line is shorter.
+fn slice_columns(line: &str, start: usize, end: usize) -> &str {
+    line.get(start..end.min(line.len())).unwrap_or_default()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Maze {
     grid: Grid<Tile>,
     warps: HashMap<Position, (Position, i32)>,
+    labels: HashMap<Position, (char, char)>,
     start: Option<Position>,
     goal: Option<Position>,
 }
@@ -359,31 +408,27 @@ impl FromStr for Maze {
         for (r, line) in input.lines().enumerate() {
             for (c, ch) in line.bytes().enumerate() {
                 match measurement.locate(r, c) {
-                    MeasurePosition::GridBorder(_, pos) => {
-                        if ch.is_ascii_uppercase() {
-                            match &mut grid[pos] {
-                                Tile::Portal(a, b) => {
-                                    *b = ch as char;
-                                    portals.entry((*a, *b)).or_default().push((pos, -1));
-                                }
-                                tile @ Tile::Open => *tile = Tile::Portal(ch as char, '_'),
-                                tile => {
-                                    return Err(ParseError::InvalidPortalPosition);
-                                }
+                    MeasurePosition::GridBorder(_, pos) if ch.is_ascii_uppercase() => {
+                        match &mut grid[pos] {
+                            Tile::Portal(a, b) => {
+                                *b = ch as char;
+                                portals.entry((*a, *b)).or_default().push((pos, -1));
+                            }
+                            tile @ Tile::Open => *tile = Tile::Portal(ch as char, '_'),
+                            tile => {
+                                return Err(ParseError::InvalidPortalPosition);
                             }
                         }
                     }
-                    MeasurePosition::HoleBorder(_, pos) => {
-                        if ch.is_ascii_uppercase() {
-                            match &mut grid[pos] {
-                                Tile::Portal(a, b) => {
-                                    *b = ch as char;
-                                    portals.entry((*a, *b)).or_default().push((pos, 1));
-                                }
-                                tile @ Tile::Open => *tile = Tile::Portal(ch as char, '_'),
-                                tile => {
-                                    return Err(ParseError::InvalidPortalPosition);
-                                }
+                    MeasurePosition::HoleBorder(_, pos) if ch.is_ascii_uppercase() => {
+                        match &mut grid[pos] {
+                            Tile::Portal(a, b) => {
+                                *b = ch as char;
+                                portals.entry((*a, *b)).or_default().push((pos, 1));
+                            }
+                            tile @ Tile::Open => *tile = Tile::Portal(ch as char, '_'),
+                            tile => {
+                                return Err(ParseError::InvalidPortalPosition);
                             }
                         }
                     }
@@ -392,6 +437,10 @@ impl FromStr for Maze {
             }
         }
 
+        let labels = portals
+            .iter()
+            .flat_map(|(&label, group)| group.iter().map(move |&(pos, _)| (pos, label)))
+            .collect();
         let mut warps = HashMap::new();
         let mut unmatched = Vec::new();
         for group in portals.values() {
@@ -417,71 +466,161 @@ impl FromStr for Maze {
         Ok(Self {
             grid,
             warps,
+            labels,
             start,
             goal,
         })
     }
 }
 
+impl Maze {
+    fn label_at(&self, pos: Position) -> Option<(char, char)> {
+        self.labels.get(&pos).copied()
+    }
+}
+
 #[aoc_generator(day20)]
 fn parse(input: &str) -> Result<Maze, ParseError> {
     input.parse()
 }
 
 #[aoc(day20, part1)]
-fn part_1(maze: &Maze) -> usize {
-    let mut pending: VecDeque<(Position, usize)> = [(maze.start.unwrap(), 0)].into();
+fn part_1(maze: &Maze) -> Option<usize> {
+    solve_part1_path(maze).map(|(dist, _)| dist)
+}
+
+/// Shortest route from the start to the goal. Taking a portal counts as a step, and shows up as
+/// two consecutive positions that are not next to each other.
+fn solve_part1_path(maze: &Maze) -> Option<(usize, Vec<Position>)> {
+    let start = maze.start?;
+    let goal = maze.goal?;
+    let mut pending: VecDeque<Position> = [start].into();
+    let mut came_from = HashMap::new();
+    let mut visited = HashSet::from([start]);
+    while let Some(pos) = pending.pop_front() {
+        if pos == goal {
+            let mut path = vec![pos];
+            let mut pos = pos;
+            while let Some(&prev) = came_from.get(&pos) {
+                path.push(prev);
+                pos = prev;
+            }
+            path.reverse();
+            return Some((path.len() - 1, path));
+        }
+        let twin = maze.warps.get(&pos).map(|&(twin, _)| twin);
+        let steps = Direction::all()
+            .map(|dir| pos + dir)
+            .into_iter()
+            .filter(|&next| maze.grid[next].is_passable());
+        for next in twin.into_iter().chain(steps) {
+            if visited.insert(next) {
+                came_from.insert(next, pos);
+                pending.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+fn reachable_from_start(maze: &Maze) -> HashSet<Position> {
+    let mut pending: VecDeque<Position> = [maze.start.unwrap()].into();
     let mut visited = HashSet::new();
-    while let Some((pos, dist)) = pending.pop_front() {
+    while let Some(pos) = pending.pop_front() {
         if !visited.insert(pos) {
             continue;
         }
-        if pos == maze.goal.unwrap() {
-            return dist;
-        }
         if let Some(&(twin, _)) = maze.warps.get(&pos)
             && !visited.contains(&twin)
         {
-            pending.push_back((twin, dist + 1));
+            pending.push_back(twin);
         }
         for dir in Direction::all() {
             let next = pos + dir;
             if maze.grid[next].is_passable() && !visited.contains(&next) {
-                pending.push_back((next, dist + 1));
+                pending.push_back(next);
             }
         }
     }
-    0
+    visited
 }
 
 #[aoc(day20, part2)]
-fn part_2(maze: &Maze) -> usize {
-    let mut pending: VecDeque<(Position, u32, usize)> = [(maze.start.unwrap(), 0, 0)].into();
+fn part_2(maze: &Maze) -> Option<usize> {
+    let start = maze.start?;
+    let goal = maze.goal?;
+    let graph = portal_graph(maze);
+    // Going deeper than there are portals will not lead anywhere new
+    let max_depth = u32::try_from(maze.warps.len()).unwrap();
+    let mut pending = BinaryHeap::new();
+    pending.push((Reverse(0), start, 0_u32));
     let mut visited = HashSet::new();
-    let mut max_depth = 0;
-    while let Some((pos, depth, dist)) = pending.pop_front() {
+    while let Some((Reverse(dist), pos, depth)) = pending.pop() {
         if !visited.insert((pos, depth)) {
             continue;
         }
-        max_depth = max_depth.max(depth);
-        if (pos, depth) == (maze.goal.unwrap(), 0) {
-            println!("Max depth {max_depth}");
-            return dist;
+        if (pos, depth) == (goal, 0) {
+            return Some(dist);
         }
         if let Some(&(twin, delta)) = maze.warps.get(&pos)
             && let Some(twindepth) = depth.checked_add_signed(delta)
+            && twindepth <= max_depth
             && !visited.contains(&(twin, twindepth))
         {
-            pending.push_back((twin, twindepth, dist + 1));
+            pending.push((Reverse(dist + 1), twin, twindepth));
         }
-        for dir in Direction::all() {
-            let next = pos + dir;
-            if maze.grid[next].is_passable() && !visited.contains(&(next, depth)) {
-                pending.push_back((next, depth, dist + 1));
+        for &(next, delta) in &graph[&pos] {
+            if !visited.contains(&(next, depth)) {
+                pending.push((Reverse(dist + delta), next, depth));
             }
         }
     }
-    0
+    None
+}
+
+pub struct Day20;
+
+impl Solver for Day20 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
+}
+
+/// Walking distances between the start, the goal and every portal, without using any portals.
+fn portal_graph(maze: &Maze) -> HashMap<Position, Vec<(Position, usize)>> {
+    let nodes = maze
+        .warps
+        .keys()
+        .copied()
+        .chain(maze.start)
+        .chain(maze.goal)
+        .collect::<HashSet<_>>();
+    let mut graph = HashMap::new();
+    for &node in &nodes {
+        let mut edges = Vec::new();
+        let mut pending: VecDeque<(Position, usize)> = [(node, 0)].into();
+        let mut visited = HashSet::new();
+        while let Some((pos, dist)) = pending.pop_front() {
+            if !visited.insert(pos) {
+                continue;
+            }
+            if pos != node && nodes.contains(&pos) {
+                edges.push((pos, dist));
+            }
+            for dir in Direction::all() {
+                let next = pos + dir;
+                if maze.grid[next].is_passable() && !visited.contains(&next) {
+                    pending.push_back((next, dist + 1));
+                }
+            }
+        }
+        graph.insert(node, edges);
+    }
+    graph
 }
 
 #[cfg(test)]
@@ -626,6 +765,24 @@ mod tests {
         assert_eq!(count_void, 2 * 2 * 4 + 3); // 4 outside corners + center
     }
 
+    #[test]
+    fn test_irregular_margins() {
+        let input = fix_example(EXAMPLE1)
+            .lines()
+            .map(|l| format!("   {}", l.trim_end()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let measure = Measurement::measure(&input);
+        assert_eq!(measure.margin_x, 5);
+        assert_eq!(measure.margin_y, 2);
+        assert_eq!(measure.grid_width, 17);
+        assert_eq!(measure.hole_offset_x, 10);
+        assert_eq!(measure.hole_width, 7);
+        let maze = parse(&input).unwrap();
+        assert_eq!(maze, parse(&fix_example(EXAMPLE1)).unwrap());
+        assert_eq!(part_1(&maze), Some(23));
+    }
+
     #[test]
     fn test_parse() {
         let res = parse(&fix_example(EXAMPLE1)).unwrap();
@@ -646,17 +803,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_label_at() {
+        let maze = parse(&fix_example(EXAMPLE1)).unwrap();
+        assert_eq!(maze.label_at(maze.start.unwrap()), Some(('A', 'A')));
+        assert_eq!(maze.label_at(maze.goal.unwrap()), Some(('Z', 'Z')));
+        assert_eq!(maze.label_at(Position::new(0, 6)), Some(('B', 'C')));
+        assert_eq!(maze.label_at(Position::new(7, 4)), Some(('B', 'C')));
+        assert_eq!(maze.label_at(Position::new(9, 10)), Some(('F', 'G')));
+        assert_eq!(maze.label_at(Position::new(7, 1)), None);
+    }
+
     #[test_case(EXAMPLE1 => 23)]
     #[test_case(EXAMPLE2 => 58)]
     fn test_part_1(input: &str) -> usize {
         let maze = parse(&fix_example(input)).unwrap();
-        part_1(&maze)
+        part_1(&maze).unwrap()
+    }
+
+    #[test]
+    fn test_walled_off_goal() {
+        let input = fix_example(EXAMPLE1).replace("FG..#########.....#", "FG..##########....#");
+        let maze = parse(&input).unwrap();
+        assert!(!reachable_from_start(&maze).contains(&maze.goal.unwrap()));
+        assert_eq!(part_1(&maze), None);
+        assert_eq!(part_2(&maze), None);
+    }
+
+    #[test]
+    fn test_solve_part1_path() {
+        let maze = parse(&fix_example(EXAMPLE1)).unwrap();
+        let (dist, path) = solve_part1_path(&maze).unwrap();
+        assert_eq!(dist, 23);
+        assert_eq!(path.len(), dist + 1);
+        let portals = path
+            .windows(2)
+            .filter(|w| w[0].x.abs_diff(w[1].x) + w[0].y.abs_diff(w[1].y) != 1)
+            .map(|w| maze.label_at(w[0]).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(portals, [('B', 'C'), ('D', 'E'), ('F', 'G')]);
+    }
+
+    #[cfg(feature = "rgba")]
+    #[test]
+    fn test_to_rgba() {
+        let maze = parse(&fix_example(EXAMPLE1)).unwrap();
+        let (pixels, width, height) = crate::render::to_rgba(&maze.grid, |tile| {
+            if tile.is_passable() {
+                [255; 4]
+            } else {
+                [0, 0, 0, 255]
+            }
+        });
+        assert_eq!((width, height), (maze.grid.cols, maze.grid.rows));
+        assert_eq!(pixels.len(), width * height * 4);
+    }
+
+    #[test]
+    fn test_reachable_from_start() {
+        let maze = parse(&fix_example(EXAMPLE1)).unwrap();
+        let reachable = reachable_from_start(&maze);
+        assert!(reachable.contains(&maze.goal.unwrap()));
     }
 
-    #[test_case(EXAMPLE1 => 26)]
-    // EXAMPLE2 would never finish, and would eventually run out of memory
-    #[test_case(EXAMPLE3 => 396)]
-    fn test_part_2(input: &str) -> usize {
+    #[test_case(EXAMPLE1 => Some(26))]
+    #[test_case(EXAMPLE2 => None; "no recursive path")]
+    #[test_case(EXAMPLE3 => Some(396))]
+    fn test_part_2(input: &str) -> Option<usize> {
         let maze = parse(&fix_example(input)).unwrap();
         part_2(&maze)
     }
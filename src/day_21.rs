@@ -1,7 +1,11 @@
-use std::fmt::{Display, Write};
+use std::fmt::Display;
 use std::num::ParseIntError;
+use std::str::FromStr;
+
+use thiserror::Error;
 
 use crate::machine::{Machine, MachineError, Value, parse_program};
+use crate::solver::{Answer, Solver};
 
 #[allow(unused)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -31,6 +35,54 @@ enum Reg {
     I,
 }
 
+#[derive(Debug, Error)]
+enum ParseError {
+    #[error("Syntax error: {0:?}")]
+    SyntaxError(String),
+    #[error("Unknown instruction: {0:?}")]
+    UnknownInstruction(String),
+    #[error("Unknown register: {0:?}")]
+    UnknownRegister(String),
+    #[error("Can only write to T or J, not {0}")]
+    ReadOnlyRegister(Reg),
+}
+
+#[derive(Debug, Error)]
+enum SpringError {
+    #[error("Springscript has {0} instructions, but at most {MAX_INSTRUCTIONS} are allowed")]
+    TooManyInstructions(usize),
+    #[error("The last instruction has to write to J")]
+    MissingJump,
+    #[error("Can only write to T or J, not {0}")]
+    ReadOnlyRegister(Reg),
+    #[error(transparent)]
+    MachineError(#[from] MachineError),
+}
+
+/// Number of instructions that fit in the springscript memory.
+const MAX_INSTRUCTIONS: usize = 15;
+
+impl FromStr for Reg {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "T" => Self::T,
+            "J" => Self::J,
+            "A" => Self::A,
+            "B" => Self::B,
+            "C" => Self::C,
+            "D" => Self::D,
+            "E" => Self::E,
+            "F" => Self::F,
+            "G" => Self::G,
+            "H" => Self::H,
+            "I" => Self::I,
+            _ => return Err(ParseError::UnknownRegister(s.to_string())),
+        })
+    }
+}
+
 impl Display for Reg {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{self:?}")
@@ -44,6 +96,45 @@ enum Instruction {
     Not(Reg, Reg),
 }
 
+impl Instruction {
+    const fn target(self) -> Reg {
+        match self {
+            Self::And(_, dst) | Self::Or(_, dst) | Self::Not(_, dst) => dst,
+        }
+    }
+
+    /// Parses springscript with one instruction per line. Blank lines are skipped.
+    #[allow(unused, reason = "tests")]
+    fn parse_program(src: &str) -> Result<Vec<Self>, ParseError> {
+        src.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let &[op, src, dst] = s.split_whitespace().collect::<Vec<_>>().as_slice() else {
+            return Err(ParseError::SyntaxError(s.to_string()));
+        };
+        let src = src.parse()?;
+        let dst = dst.parse()?;
+        if !matches!(dst, Reg::T | Reg::J) {
+            return Err(ParseError::ReadOnlyRegister(dst));
+        }
+        Ok(match op {
+            "AND" => Self::And(src, dst),
+            "OR" => Self::Or(src, dst),
+            "NOT" => Self::Not(src, dst),
+            _ => return Err(ParseError::UnknownInstruction(op.to_string())),
+        })
+    }
+}
+
 impl Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match *self {
@@ -86,14 +177,13 @@ impl<'a> SpringDroid<'a> {
         &mut self,
         instructions: &[Instruction],
         mode: Mode,
-    ) -> Result<Option<Value>, MachineError> {
+    ) -> Result<Option<Value>, SpringError> {
+        validate(instructions)?;
         self.machine.reset(self.program);
-        let mut buf = String::new();
         for instr in instructions {
-            writeln!(&mut buf, "{instr}").unwrap();
+            self.machine.extend_ascii_line(&instr.to_string());
         }
-        writeln!(&mut buf, "{mode}").unwrap();
-        self.machine.inputs.extend(buf.bytes().map(Value::from));
+        self.machine.extend_ascii_line(&mode.to_string());
         self.machine.run_until_stopped()?;
         let mut output = Vec::new();
         for &val in &self.machine.outputs {
@@ -115,69 +205,308 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
     parse_program(input)
 }
 
+/// When jumping, it will jump to the tile at distnace 4m, same as the 'D' register.
+/// The logic is J = (!A | !B | !C) & D
+/// That is, if there are any gaps, and a jump is safe, do it.
+const WALK_SCRIPT: [Instruction; 6] = [
+    Instruction::Not(Reg::D, Reg::T),
+    Instruction::Or(Reg::A, Reg::T),
+    Instruction::And(Reg::B, Reg::T),
+    Instruction::And(Reg::C, Reg::T),
+    Instruction::Not(Reg::T, Reg::J),
+    Instruction::And(Reg::D, Reg::J),
+];
+
 #[aoc(day21, part1)]
 fn part_1(program: &[Value]) -> Value {
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> Value {
     let mut droid = SpringDroid::new(program);
-    // When jumping, it will jump to the tile at distnace 4m, same as the 'D' register.
-    // The logic is J = (!A | !B | !C) & D
-    // That is, if there are any gaps, and a jump is safe, do it.
-    droid
-        .execute(
-            &[
-                Instruction::Not(Reg::D, Reg::T),
-                Instruction::Or(Reg::A, Reg::T),
-                Instruction::And(Reg::B, Reg::T),
-                Instruction::And(Reg::C, Reg::T),
-                Instruction::Not(Reg::T, Reg::J),
-                Instruction::And(Reg::D, Reg::J),
-            ],
-            Mode::Walk,
-        )
-        .unwrap()
-        .unwrap()
+    droid.machine.log = trace;
+    droid.execute(&WALK_SCRIPT, Mode::Walk).unwrap().unwrap()
+}
+
+/// Checks that the springscript fits in memory, only writes to `T` and `J`, and ends by writing to
+/// `J`.
+fn validate(instructions: &[Instruction]) -> Result<(), SpringError> {
+    if instructions.len() > MAX_INSTRUCTIONS {
+        return Err(SpringError::TooManyInstructions(instructions.len()));
+    }
+    if let Some(instr) = instructions
+        .iter()
+        .find(|instr| !matches!(instr.target(), Reg::T | Reg::J))
+    {
+        return Err(SpringError::ReadOnlyRegister(instr.target()));
+    }
+    if instructions.last().map(|instr| instr.target()) != Some(Reg::J) {
+        return Err(SpringError::MissingJump);
+    }
+    Ok(())
+}
+
+/// Runs the springscript against a single set of sensor readings, and returns whether the droid
+/// would jump. `sensors[0]` is `A`, and any sensor beyond the end of the slice reads as a gap. The
+/// script has to have passed `validate`.
+fn simulate(instructions: &[Instruction], sensors: &[bool]) -> bool {
+    let mut temp = false;
+    let mut jump = false;
+    for &instr in instructions {
+        let (src, dst) = match instr {
+            Instruction::And(src, dst) | Instruction::Or(src, dst) | Instruction::Not(src, dst) => {
+                (src, dst)
+            }
+        };
+        let value = match src {
+            Reg::T => temp,
+            Reg::J => jump,
+            sensor => sensors
+                .get(sensor as usize - Reg::A as usize)
+                .copied()
+                .unwrap_or(false),
+        };
+        let target = match dst {
+            Reg::T => &mut temp,
+            Reg::J => &mut jump,
+            _ => unreachable!("validated scripts only write to T or J"),
+        };
+        match instr {
+            Instruction::And(..) => *target &= value,
+            Instruction::Or(..) => *target |= value,
+            Instruction::Not(..) => *target = !value,
+        }
+    }
+    jump
+}
+
+/// Jump decision for every combination of the first `sensors` sensors. In row `n`, sensor `k`
+/// (with `A` as `0`) reads ground when bit `k` of `n` is set.
+#[allow(unused, reason = "tests")]
+fn truth_table(
+    instructions: &[Instruction],
+    sensors: usize,
+) -> Result<Vec<(Vec<bool>, bool)>, SpringError> {
+    validate(instructions)?;
+    Ok((0..1_usize << sensors)
+        .map(|row| {
+            let readings = (0..sensors)
+                .map(|k| row & (1 << k) != 0)
+                .collect::<Vec<_>>();
+            let jump = simulate(instructions, &readings);
+            (readings, jump)
+        })
+        .collect())
 }
 
 #[aoc(day21, part2)]
 fn part_2(program: &[Value]) -> Value {
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> Value {
     let mut droid = SpringDroid::new(program);
-    // ABCDEFGHI
-    // .???????? -- Imminent gap, must jump
-    // ??.##???# -- Jump-Step-Jump to exit
-    // ??.#???#? -- Jump-Jump-Jump to exit
-    // ?.?##???# -- Jump-Step-Jump to exit
-    // ?.?#???#? -- Jump-Jump-Jump to exit
-    //
-    // Combined: .???????? OR ?(?.|.?)#(???#?|#???#)
-    //
-    // Logic: !A | (!B | !C) & D & (H | E & I)
-    //
-    // (!B | !C) & D
-    // (!D | !B | !C) & D   -- Adding D does not change result.
-    // !!(!D | !B | !C) & D -- Double negation.
-    // !(D & B & C) & D     -- De Morgan.
-    // !(!!D & B & C) & D   -- Double negation, unable to just copy.
-    //
-    // (H | E & I)
-    // (H | !H & E & I)     -- Adding !H does not change result.
-    droid
-        .execute(
-            &[
-                Instruction::Not(Reg::H, Reg::J), // J = !H
-                Instruction::And(Reg::I, Reg::J), // J = I & !H
-                Instruction::And(Reg::E, Reg::J), // J = E & I & !H
-                Instruction::Or(Reg::H, Reg::J), // J = H | (E & I & !H) = H | (E & I)
-                Instruction::Not(Reg::D, Reg::T), // T = !D
-                Instruction::Not(Reg::T, Reg::T), // T = !!D = D
-                Instruction::And(Reg::C, Reg::T), // T = C & D
-                Instruction::And(Reg::B, Reg::T), // T = B & C & D
-                Instruction::Not(Reg::T, Reg::T), // T = !(B & C & D) = (!B | !C | !D)
-                Instruction::And(Reg::D, Reg::T), // T = D & (!B | !C | !D) = D & (!B | !C)
-                Instruction::And(Reg::T, Reg::J), // J = D & (!B | !C) & (H | (E & I))
-                Instruction::Not(Reg::A, Reg::T), // T = !A
-                Instruction::Or(Reg::T, Reg::J), // J = !A | D & (!B | !C) & (H | (E & I))
-            ],
-            Mode::Run,
+    droid.machine.log = trace;
+    droid.execute(&RUN_SCRIPT, Mode::Run).unwrap().unwrap()
+}
+
+#[derive(Default)]
+pub struct Day21 {
+    trace: bool,
+}
+
+impl Solver for Day21 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
+// ABCDEFGHI
+// .???????? -- Imminent gap, must jump
+// ??.##???# -- Jump-Step-Jump to exit
+// ??.#???#? -- Jump-Jump-Jump to exit
+// ?.?##???# -- Jump-Step-Jump to exit
+// ?.?#???#? -- Jump-Jump-Jump to exit
+//
+// Combined: .???????? OR ?(?.|.?)#(???#?|#???#)
+//
+// Logic: !A | (!B | !C) & D & (H | E & I)
+//
+// (!B | !C) & D
+// (!D | !B | !C) & D   -- Adding D does not change result.
+// !!(!D | !B | !C) & D -- Double negation.
+// !(D & B & C) & D     -- De Morgan.
+// !(!!D & B & C) & D   -- Double negation, unable to just copy.
+//
+// (H | E & I)
+// (H | !H & E & I)     -- Adding !H does not change result.
+const RUN_SCRIPT: [Instruction; 13] = [
+    Instruction::Not(Reg::H, Reg::J), // J = !H
+    Instruction::And(Reg::I, Reg::J), // J = I & !H
+    Instruction::And(Reg::E, Reg::J), // J = E & I & !H
+    Instruction::Or(Reg::H, Reg::J),  // J = H | (E & I & !H) = H | (E & I)
+    Instruction::Not(Reg::D, Reg::T), // T = !D
+    Instruction::Not(Reg::T, Reg::T), // T = !!D = D
+    Instruction::And(Reg::C, Reg::T), // T = C & D
+    Instruction::And(Reg::B, Reg::T), // T = B & C & D
+    Instruction::Not(Reg::T, Reg::T), // T = !(B & C & D) = (!B | !C | !D)
+    Instruction::And(Reg::D, Reg::T), // T = D & (!B | !C | !D) = D & (!B | !C)
+    Instruction::And(Reg::T, Reg::J), // J = D & (!B | !C) & (H | (E & I))
+    Instruction::Not(Reg::A, Reg::T), // T = !A
+    Instruction::Or(Reg::T, Reg::J),  // J = !A | D & (!B | !C) & (H | (E & I))
+];
+
+/// Whether a droid running the script in walk mode makes it across the hull. `hull[0]` is where
+/// the droid starts, and the hull continues with ground after the end of the slice.
+#[allow(unused, reason = "tests")]
+fn simulate_walk(instructions: &[Instruction], hull: &[bool]) -> Result<bool, SpringError> {
+    validate(instructions)?;
+    Ok(survives(instructions, hull, 4))
+}
+
+/// Like `simulate_walk`, but in run mode, where the droid can see nine tiles ahead.
+#[allow(unused, reason = "tests")]
+fn simulate_run(instructions: &[Instruction], hull: &[bool]) -> Result<bool, SpringError> {
+    validate(instructions)?;
+    Ok(survives(instructions, hull, 9))
+}
+
+/// Moves the droid one step at a time, or four when it jumps, until it either falls into a gap
+/// or reaches the end of the hull. The script has to have passed `validate`.
+fn survives(instructions: &[Instruction], hull: &[bool], sensors: usize) -> bool {
+    let ground = |pos: usize| hull.get(pos).copied().unwrap_or(true);
+    let mut pos = 0;
+    while pos + 1 < hull.len() {
+        let readings = (pos + 1..=pos + sensors).map(ground).collect::<Vec<_>>();
+        let jump = simulate(instructions, &readings);
+        pos += if jump { 4 } else { 1 };
+        if !ground(pos) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn test_parse_program() {
+        let text = WALK_SCRIPT
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(Instruction::parse_program(&text).unwrap(), WALK_SCRIPT);
+        assert_eq!(
+            Instruction::parse_program("NOT A J\n\nOR E T\n").unwrap(),
+            [
+                Instruction::Not(Reg::A, Reg::J),
+                Instruction::Or(Reg::E, Reg::T)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_program_errors() {
+        assert!(matches!(
+            Instruction::parse_program("XOR A J"),
+            Err(ParseError::UnknownInstruction(_))
+        ));
+        assert!(matches!(
+            Instruction::parse_program("AND X J"),
+            Err(ParseError::UnknownRegister(_))
+        ));
+        assert!(matches!(
+            Instruction::parse_program("AND A B"),
+            Err(ParseError::ReadOnlyRegister(Reg::B))
+        ));
+        assert!(matches!(
+            Instruction::parse_program("AND A"),
+            Err(ParseError::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn test_execute_validation() {
+        let mut droid = SpringDroid::new(&[99]);
+        let too_long = [Instruction::Not(Reg::A, Reg::J); 16];
+        assert!(matches!(
+            droid.execute(&too_long, Mode::Walk),
+            Err(SpringError::TooManyInstructions(16))
+        ));
+        let ends_in_temp = [
+            Instruction::Not(Reg::A, Reg::J),
+            Instruction::Or(Reg::B, Reg::T),
+        ];
+        assert!(matches!(
+            droid.execute(&ends_in_temp, Mode::Walk),
+            Err(SpringError::MissingJump)
+        ));
+        assert!(matches!(
+            droid.execute(&[], Mode::Walk),
+            Err(SpringError::MissingJump)
+        ));
+        let writes_sensor = [
+            Instruction::Not(Reg::A, Reg::B),
+            Instruction::Not(Reg::B, Reg::J),
+        ];
+        assert!(matches!(
+            droid.execute(&writes_sensor, Mode::Walk),
+            Err(SpringError::ReadOnlyRegister(Reg::B))
+        ));
+        assert!(matches!(
+            simulate_walk(&writes_sensor, &[true; 5]),
+            Err(SpringError::ReadOnlyRegister(Reg::B))
+        ));
+        assert!(matches!(
+            truth_table(&writes_sensor, 4),
+            Err(SpringError::ReadOnlyRegister(Reg::B))
+        ));
+        assert!(droid.execute(&WALK_SCRIPT, Mode::Walk).is_ok());
+    }
+
+    fn hull(text: &str) -> Vec<bool> {
+        text.bytes().map(|ch| ch == b'#').collect()
+    }
+
+    #[test_case("#####.#####" => (true, true); "single gap")]
+    #[test_case("#####...#########" => (true, true); "wide gap")]
+    #[test_case("#####.#..########" => (true, true); "two gaps")]
+    #[test_case("#####.#.##.#.####" => (false, true); "walk jumps too early")]
+    #[test_case("#####.#.#...#.###" => (false, true); "walk lands in a trap")]
+    #[test_case("#####....########" => (false, false); "too wide to jump")]
+    fn test_simulate(text: &str) -> (bool, bool) {
+        let hull = hull(text);
+        (
+            simulate_walk(&WALK_SCRIPT, &hull).unwrap(),
+            simulate_run(&RUN_SCRIPT, &hull).unwrap(),
         )
-        .unwrap()
-        .unwrap()
+    }
+
+    #[test]
+    fn test_truth_table_walk() {
+        let table = truth_table(&WALK_SCRIPT, 4).unwrap();
+        assert_eq!(table.len(), 16);
+        for (sensors, jump) in table {
+            let &[a, b, c, d] = sensors.as_slice() else {
+                panic!("Expected four sensors");
+            };
+            assert_eq!(jump, !(a && b && c) && d, "{sensors:?}");
+        }
+    }
 }
@@ -4,27 +4,38 @@ use std::str::FromStr;
 
 use thiserror::Error;
 
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
-enum ParseError {
+pub enum ParseError {
     #[error("Syntax error")]
     SyntaxError,
     #[error(transparent)]
     InvalidNumber(#[from] ParseIntError),
 }
 
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ShuffleError {
+    #[error("{value} has no inverse modulo {modulo}")]
+    NotInvertible { value: u64, modulo: u64 },
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Operation {
+pub enum Operation {
     /// Reverse; card k -> position (10.006 - k)
     DealIntoNewDeck,
     /// Rotate; card k -> position (n + k) % 10.007
     Cut(i64),
     /// card k -> position (n * k) % 10.007
+    ///
+    /// The increment must be coprime to the deck size, or several cards would land on the same
+    /// position.
     DealWithIncrement(u64),
 }
 
 impl Operation {
-    fn apply(self, deck: Shuffle) -> Shuffle {
-        match self {
+    fn apply(self, deck: Shuffle) -> Result<Shuffle, ShuffleError> {
+        Ok(match self {
             Self::DealIntoNewDeck => {
                 let last = deck.card_at_position(deck.size - 1);
                 let second_last = deck.card_at_position(deck.size - 2);
@@ -37,10 +48,10 @@ impl Operation {
                 Shuffle::new(first, deck.step, deck.size)
             }
             Self::DealWithIncrement(scale) => {
-                let step = modular_mul(deck.step, modular_inverse(scale, deck.size), deck.size);
+                let step = modular_mul(deck.step, modular_inverse(scale, deck.size)?, deck.size);
                 Shuffle::new(deck.first, step, deck.size)
             }
-        }
+        })
     }
 }
 
@@ -66,40 +77,71 @@ fn parse(input: &str) -> Result<Vec<Operation>, ParseError> {
 }
 
 #[aoc(day22, part1)]
-fn part_1(operations: &[Operation]) -> u64 {
+fn part_1(operations: &[Operation]) -> Result<u64, ShuffleError> {
     position_of_card(operations, 2019, 10_007)
 }
 
-fn position_of_card(operations: &[Operation], card: u64, deck_size: u64) -> u64 {
-    let mut poly = Shuffle::new(0, 1, deck_size);
-    for op in operations {
-        poly = op.apply(poly);
-    }
-    poly.position_of_card(card)
+fn position_of_card(
+    operations: &[Operation],
+    card: u64,
+    deck_size: u64,
+) -> Result<u64, ShuffleError> {
+    compose(operations, deck_size)?.position_of_card(card)
+}
+
+/// Reduces the whole list of operations to a single affine map over the deck.
+pub fn compose(operations: &[Operation], deck_size: u64) -> Result<Shuffle, ShuffleError> {
+    let identity = Shuffle::new(0, 1, deck_size);
+    operations
+        .iter()
+        .try_fold(identity, |shuffle, op| op.apply(shuffle))
 }
 
 #[aoc(day22, part2)]
-fn part_2(operations: &[Operation]) -> u64 {
+fn part_2(operations: &[Operation]) -> Result<u64, ShuffleError> {
     repeated_card_at_position(operations, 2020, 119_315_717_514_047, 101_741_582_076_661)
 }
 
+pub struct Day22;
+
+impl Solver for Day22 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
+    }
+}
+
 fn repeated_card_at_position(
     operations: &[Operation],
     target_position: u64,
     deck_size: u64,
     shuffles: u64,
-) -> u64 {
-    let mut shuffle = Shuffle::new(0, 1, deck_size);
-    for op in operations {
-        shuffle = op.apply(shuffle);
-    }
+) -> Result<u64, ShuffleError> {
+    let shuffle = compose(operations, deck_size)?;
+
+    let shuffle_iterated = shuffle.iterated(shuffles);
+    Ok(shuffle_iterated.card_at_position(target_position))
+}
+
+#[allow(unused, reason = "tests")]
+fn card_position_after_shuffles(
+    operations: &[Operation],
+    card: u64,
+    deck_size: u64,
+    shuffles: u64,
+) -> Result<u64, ShuffleError> {
+    let shuffle = compose(operations, deck_size)?;
 
     let shuffle_iterated = shuffle.iterated(shuffles);
-    shuffle_iterated.card_at_position(target_position)
+    shuffle_iterated.position_after(card)
 }
 
+/// The card at position `pos` is `(step * pos + first) mod size`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Shuffle {
+pub struct Shuffle {
     first: u64,
     step: u64,
     size: u64,
@@ -114,38 +156,78 @@ impl Shuffle {
         (modular_mul(position, self.step, self.size) + self.first) % self.size
     }
 
-    fn position_of_card(self, card: u64) -> u64 {
+    fn position_of_card(self, card: u64) -> Result<u64, ShuffleError> {
         // card = first + step * pos
         // pos * step == card - first
         // pos == (card - first) * step^-1
-        modular_mul(
+        Ok(modular_mul(
             (card + self.size - self.first) % self.size,
-            modular_inverse(self.step, self.size),
+            modular_inverse(self.step, self.size)?,
             self.size,
-        )
+        ))
     }
 
-    fn iterated(self, times: u64) -> Self {
-        // f(x) = (a * x + b) % m
-        // f(f(x)) = (a^2 * x + (a + 1) * b) % m
-        // f(f(f(x))) = (a^3 * x + (a^2 + a + 1) * b) % m
+    /// The forward map, from card to the position it ends up in. Same result as
+    /// `position_of_card`, but evaluated as an affine map of its own.
+    fn position_after(self, card: u64) -> Result<u64, ShuffleError> {
+        Ok(self.inverse()?.card_at_position(card))
+    }
+
+    /// Positions the cards `start..start + len` end up in, in the order of the cards.
+    #[allow(unused, reason = "tests")]
+    fn map_range(self, start: u64, len: u64) -> Result<Vec<u64>, ShuffleError> {
+        let forward = self.inverse()?;
+        Ok((start..start + len)
+            .map(|card| forward.card_at_position(card))
+            .collect())
+    }
 
-        // sum(a^k,k=0..n-1) = (a^n - 1)/(a - 1)
+    fn inverse(self) -> Result<Self, ShuffleError> {
+        // card = first + step * pos
+        // pos = step^-1 * card - step^-1 * first
+        let step = modular_inverse(self.step, self.size)?;
+        let first = (self.size - modular_mul(step, self.first, self.size)) % self.size;
+        Ok(Self::new(first, step, self.size))
+    }
 
-        // (f^n)(x) = (a^n * x + (a^n - 1)/(a - 1) * b) % m
-        let Self { step, first, size } = self;
-        let step2 = modular_pow(step, times, size);
-        let first2_scale = modular_mul(step2 - 1, modular_inverse(step - 1, size), size);
-        let first2 = modular_mul(first2_scale, first, size);
-        Self::new(first2, step2, size)
+    /// Applies `self` and then `other`.
+    fn then(self, other: Self) -> Self {
+        // self(x) = a * x + b, other(y) = c * y + d
+        // other(self(x)) = (a * c) * x + (b * c + d)
+        let size = self.size;
+        Self::new(
+            (modular_mul(self.first, other.step, size) + other.first) % size,
+            modular_mul(self.step, other.step, size),
+            size,
+        )
+    }
+
+    /// The shuffle repeated `times` times, by squaring and multiplying. Works for any deck size,
+    /// unlike the closed form `(a^n - 1)/(a - 1)`, which needs `a - 1` to be invertible.
+    fn iterated(self, times: u64) -> Self {
+        let mut res = Self::new(0, 1, self.size);
+        let mut base = self;
+        let mut pow = times;
+        while pow > 0 {
+            if pow & 1 == 0 {
+                base = base.then(base);
+                pow /= 2;
+            } else {
+                res = res.then(base);
+                pow -= 1;
+            }
+        }
+        res
     }
 }
 
 impl Display for Shuffle {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_list()
-            .entries((0..self.size).map(|pos| self.card_at_position(pos)))
-            .finish()
+        write!(
+            f,
+            "pos -> ({} * pos + {}) mod {}",
+            self.step, self.first, self.size
+        )
     }
 }
 
@@ -158,35 +240,20 @@ fn modular_mul(mut a: u64, mut b: u64, modulo: u64) -> u64 {
     )
 }
 
-fn modular_pow(a: u64, n: u64, m: u64) -> u64 {
-    match n {
-        0 => 1,
-        1 => n % m,
-        _ => {
-            let mut res = 1;
-            let mut base = a;
-            let mut pow = n;
-            while pow > 0 {
-                if pow & 1 == 0 {
-                    base = modular_mul(base, base, m);
-                    pow /= 2;
-                } else {
-                    res = modular_mul(res, base, m);
-                    pow -= 1;
-                }
-            }
-            res
-        }
+/// Only defined when `gcd(a, m) == 1`.
+fn modular_inverse(a: u64, m: u64) -> Result<u64, ShuffleError> {
+    let (gcd, x, _) = egcd(a, m);
+    if gcd != 1 {
+        return Err(ShuffleError::NotInvertible {
+            value: a,
+            modulo: m,
+        });
     }
-}
-
-fn modular_inverse(a: u64, m: u64) -> u64 {
-    let (_, x, _) = egcd(a, m);
-    if x < 0 {
+    Ok(if x < 0 {
         m.checked_add_signed(x).unwrap()
     } else {
         (0_u64).checked_add_signed(x).unwrap()
-    }
+    })
 }
 
 pub fn egcd(a: u64, b: u64) -> (u64, i64, i64) {
@@ -207,6 +274,8 @@ pub fn egcd(a: u64, b: u64) -> (u64, i64, i64) {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use super::*;
     use test_case::test_case;
 
@@ -259,7 +328,7 @@ mod tests {
     #[test_case(Operation::Cut(-4) => &[6, 7, 8, 9, 0, 1, 2, 3, 4, 5][..])]
     #[test_case(Operation::DealWithIncrement(3) => &[0, 7, 4, 1, 8, 5, 2, 9, 6, 3][..])]
     fn test_single(op: Operation) -> Vec<u64> {
-        let shuffle = op.apply(Shuffle::new(0, 1, 10));
+        let shuffle = op.apply(Shuffle::new(0, 1, 10)).unwrap();
         (0..10).map(|card| shuffle.card_at_position(card)).collect()
     }
 
@@ -269,15 +338,21 @@ mod tests {
     #[test_case(EXAMPLE4, 10 => &[9, 2, 5, 8, 1, 4, 7, 0, 3, 6][..])]
     fn test_poly_evaluate(input: &str, deck_size: u64) -> Vec<u64> {
         let operations = parse(input).unwrap();
-        let mut shuffle = Shuffle::new(0, 1, 10);
-        for op in &operations {
-            shuffle = op.apply(shuffle);
-        }
+        let shuffle = compose(&operations, deck_size).unwrap();
         (0..deck_size)
             .map(|card| shuffle.card_at_position(card))
             .collect()
     }
 
+    #[test_case(EXAMPLE1 => "pos -> (3 * pos + 0) mod 10")]
+    #[test_case(EXAMPLE2 => "pos -> (7 * pos + 3) mod 10")]
+    #[test_case(EXAMPLE3 => "pos -> (7 * pos + 6) mod 10")]
+    #[test_case(EXAMPLE4 => "pos -> (3 * pos + 9) mod 10")]
+    fn test_compose(input: &str) -> String {
+        let operations = parse(input).unwrap();
+        compose(&operations, 10).unwrap().to_string()
+    }
+
     #[test_case(EXAMPLE1, 10 => &[0, 3, 6, 9, 2, 5, 8, 1, 4, 7][..])]
     #[test_case(EXAMPLE2, 10 => &[3, 0, 7, 4, 1, 8, 5, 2, 9, 6][..])]
     #[test_case(EXAMPLE3, 10 => &[6, 3, 0, 7, 4, 1, 8, 5, 2, 9][..])]
@@ -286,7 +361,7 @@ mod tests {
         let operations = parse(input).unwrap();
         let mut new_deck = vec![0; usize::try_from(deck_size).unwrap()];
         for card in 0..deck_size {
-            let pos = position_of_card(&operations, card, deck_size);
+            let pos = position_of_card(&operations, card, deck_size).unwrap();
             new_deck[usize::try_from(pos).unwrap()] = card;
         }
         new_deck
@@ -304,10 +379,32 @@ mod tests {
     #[test_case(7212, 8831)]
     #[test_case(3, 10)]
     fn test_modular_inverse(num: u64, modulo: u64) {
-        let inv = modular_inverse(num, modulo);
+        let inv = modular_inverse(num, modulo).unwrap();
         assert_eq!((num * inv) % modulo, 1);
     }
 
+    #[test_case(2, 10)]
+    #[test_case(6, 9)]
+    #[test_case(0, 7)]
+    fn test_modular_inverse_not_invertible(num: u64, modulo: u64) {
+        assert_eq!(
+            modular_inverse(num, modulo),
+            Err(ShuffleError::NotInvertible { value: num, modulo })
+        );
+    }
+
+    #[test]
+    fn test_increment_sharing_factor() {
+        let operations = [Operation::DealWithIncrement(4)];
+        assert_eq!(
+            compose(&operations, 10),
+            Err(ShuffleError::NotInvertible {
+                value: 4,
+                modulo: 10
+            })
+        );
+    }
+
     #[test]
     fn test_poly_inv() {
         let poly = Shuffle::new(74, 41, 431);
@@ -318,7 +415,7 @@ mod tests {
             .collect::<Vec<_>>();
         let inverted = evaluated
             .iter()
-            .map(|&x| poly.position_of_card(x))
+            .map(|&x| poly.position_of_card(x).unwrap())
             .collect::<Vec<_>>();
         assert_eq!(inverted, original);
     }
@@ -345,16 +442,113 @@ mod tests {
         let original = (0..deck_size).collect::<Vec<_>>();
         let cards = original
             .iter()
-            .map(|&pos| repeated_card_at_position(&operations, pos, deck_size, shuffles))
+            .map(|&pos| repeated_card_at_position(&operations, pos, deck_size, shuffles).unwrap())
             .collect::<Vec<_>>();
         let positions = cards
             .iter()
             .map(|&card| {
                 (0..shuffles).fold(card, |card, _| {
-                    position_of_card(&operations, card, deck_size)
+                    position_of_card(&operations, card, deck_size).unwrap()
                 })
             })
             .collect::<Vec<_>>();
         assert_eq!(positions, original);
     }
+
+    fn brute_force(operations: &[Operation], deck_size: u64, shuffles: u64) -> Vec<u64> {
+        let mut deck = (0..deck_size).collect::<Vec<_>>();
+        for _ in 0..shuffles {
+            for &op in operations {
+                deck = match op {
+                    Operation::DealIntoNewDeck => deck.into_iter().rev().collect(),
+                    Operation::Cut(dist) => {
+                        let dist = deck_size.checked_add_signed(dist).unwrap() % deck_size;
+                        let mut cut = deck.clone();
+                        cut.rotate_left(usize::try_from(dist).unwrap());
+                        cut
+                    }
+                    Operation::DealWithIncrement(scale) => {
+                        let mut dealt = deck.clone();
+                        for (pos, &card) in (0..).zip(&deck) {
+                            let index = modular_mul(pos, scale, deck_size);
+                            dealt[usize::try_from(index).unwrap()] = card;
+                        }
+                        dealt
+                    }
+                };
+            }
+        }
+        deck
+    }
+
+    #[test_case(EXAMPLE1, 11, 10)]
+    #[test_case(EXAMPLE2, 11, 10)]
+    #[test_case(EXAMPLE3, 13, 7)]
+    #[test_case(EXAMPLE4, 17, 5)]
+    #[test_case(EXAMPLE1, 10, 2; "composite deck size")]
+    #[test_case("cut 3", 11, 4; "step of one")]
+    fn test_position_after_brute_force(input: &str, deck_size: u64, shuffles: u64) {
+        let operations = parse(input).unwrap();
+        let deck = brute_force(&operations, deck_size, shuffles);
+        for card in 0..deck_size {
+            let pos = card_position_after_shuffles(&operations, card, deck_size, shuffles).unwrap();
+            assert_eq!(deck[usize::try_from(pos).unwrap()], card);
+        }
+    }
+
+    #[test_case(EXAMPLE1, 10, 2; "composite deck size")]
+    #[test_case(EXAMPLE4, 10, 7; "composite deck size with cuts")]
+    #[test_case("cut 3", 11, 4; "step of one")]
+    fn test_repeated_card_brute_force(input: &str, deck_size: u64, shuffles: u64) {
+        let operations = parse(input).unwrap();
+        let deck = brute_force(&operations, deck_size, shuffles);
+        for (pos, &card) in (0..).zip(&deck) {
+            assert_eq!(
+                repeated_card_at_position(&operations, pos, deck_size, shuffles).unwrap(),
+                card
+            );
+        }
+    }
+
+    #[test_case(EXAMPLE1, 11, 10)]
+    #[test_case(EXAMPLE2, 11, 10)]
+    #[test_case(EXAMPLE3, 11, 10)]
+    #[test_case(EXAMPLE4, 11, 10)]
+    fn test_card_position_after_shuffles(input: &str, deck_size: u64, shuffles: u64) {
+        let operations = parse(input).unwrap();
+        let original = (0..deck_size).collect::<Vec<_>>();
+        let positions = original
+            .iter()
+            .map(|&pos| {
+                let card =
+                    repeated_card_at_position(&operations, pos, deck_size, shuffles).unwrap();
+                card_position_after_shuffles(&operations, card, deck_size, shuffles).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(positions, original);
+    }
+
+    #[test_case(EXAMPLE1, 11, 3, 5)]
+    #[test_case(EXAMPLE2, 11, 0, 11)]
+    #[test_case(EXAMPLE3, 13, 12, 1)]
+    #[test_case(EXAMPLE4, 17, 4, 9)]
+    fn test_map_range(input: &str, deck_size: u64, start: u64, len: u64) {
+        let operations = parse(input).unwrap();
+        let shuffle = compose(&operations, deck_size).unwrap();
+        let positions = shuffle.map_range(start, len).unwrap();
+        let deck = brute_force(&operations, deck_size, 1);
+        for (card, &pos) in (start..).zip(&positions) {
+            assert_eq!(deck[usize::try_from(pos).unwrap()], card);
+        }
+        let distinct = positions.iter().collect::<HashSet<_>>();
+        assert_eq!(distinct.len(), positions.len());
+    }
+
+    #[test]
+    fn test_map_range_full_deck() {
+        let operations = parse(EXAMPLE4).unwrap();
+        let mut positions = compose(&operations, 10).unwrap().map_range(0, 10).unwrap();
+        positions.sort_unstable();
+        assert_eq!(positions, (0..10).collect::<Vec<_>>());
+    }
 }
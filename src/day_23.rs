@@ -1,125 +1,331 @@
 use std::num::ParseIntError;
+use std::ops::ControlFlow;
 
 use thiserror::Error;
 
-use crate::machine::{Machine, MachineError, Value, parse_program};
+use crate::machine::{Machine, MachineError, Router, Scheduler, Value, parse_program};
+use crate::solver::{Answer, Solver};
 
 #[aoc_generator(day23)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
     parse_program(input)
 }
 
+const MACHINE_COUNT: usize = 50;
+
 #[aoc(day23, part1)]
 fn part_1(program: &[Value]) -> Value {
-    let mut sim = NetworkSimulator::new(program, 50).unwrap();
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> Value {
+    let mut sim = NetworkSimulator::new(program, MACHINE_COUNT, trace).unwrap();
     let (_, y) = sim.run_until_first_nat_package().unwrap().unwrap();
     y
 }
 
 #[aoc(day23, part2)]
 fn part_2(program: &[Value]) -> Value {
-    let mut sim = NetworkSimulator::new(program, 50).unwrap();
+    part_2_traced(program, false)
+}
+
+fn part_2_traced(program: &[Value], trace: bool) -> Value {
+    let mut sim = NetworkSimulator::new(program, MACHINE_COUNT, trace).unwrap();
     let (_, y) = sim.run_with_nat().unwrap().unwrap();
     y
 }
 
+#[derive(Default)]
+pub struct Day23 {
+    trace: bool,
+}
+
+impl Solver for Day23 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_2_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
 #[derive(Debug, Error)]
 enum RuntimeError {
     #[error("Network is idle, but no NAT package stored")]
     NoNatPackage,
+    #[error("No new NAT package in {0} rounds")]
+    NoProgress(usize),
     #[error(transparent)]
     MachineError(#[from] MachineError),
 }
 
+/// `(src, dest, x, y)`
+type Packet = (Value, Value, Value, Value);
+
 #[derive(Debug, Clone)]
 struct NetworkSimulator {
-    machines: Vec<Machine>,
-    nat_package: Option<(Value, Value)>,
+    scheduler: Scheduler,
+    nat: Nat,
+}
+
+/// Routes the packets between the machines, and stores the packets sent to its own address.
+#[derive(Debug, Clone)]
+struct Nat {
+    /// Packets sent to this address are stored by the NAT instead of delivered.
+    address: Value,
+    package: Option<(Value, Value)>,
+    first_package: Option<(Value, Value)>,
+    /// Send the stored package to address 0 whenever the network is idle.
+    wake_idle: bool,
+    /// The last package the NAT sent to address 0.
+    delivered_package: Option<(Value, Value)>,
+    /// The package the NAT sent to address 0 twice in a row.
+    repeated_package: Option<(Value, Value)>,
+    /// Give up after this many rounds in a row without a new NAT package.
+    max_stalled_rounds: usize,
+    stalled_rounds: usize,
+    round_start_package: Option<(Value, Value)>,
+    /// Record every packet sent into `packets`.
+    log: bool,
+    packets: Vec<Packet>,
+    /// Number of rounds where no machine did anything.
+    idle_cycles: usize,
+    /// The `y` of every packet the NAT has delivered to address 0.
+    deliveries: Vec<Value>,
 }
 
 impl NetworkSimulator {
-    fn new(program: &[Value], count: usize) -> Result<Self, MachineError> {
+    fn new(program: &[Value], count: usize, trace: bool) -> Result<Self, MachineError> {
+        let machines = (0..count)
+            .map(|address| {
+                let mut machine = Machine::new(program).with_log(trace);
+                machine.inputs.push_back(Value::try_from(address).unwrap());
+                machine.run_until_input()?;
+                Ok(machine)
+            })
+            .collect::<Result<_, MachineError>>()?;
         Ok(Self {
-            machines: (0..count)
-                .map(|address| {
-                    let mut machine = Machine::new(program);
-                    machine.inputs.push_back(Value::try_from(address).unwrap());
-                    machine.run_until_input()?;
-                    Ok(machine)
-                })
-                .collect::<Result<_, MachineError>>()?,
-            nat_package: None,
+            scheduler: Scheduler::new(machines),
+            nat: Nat {
+                address: 255,
+                package: None,
+                first_package: None,
+                wake_idle: false,
+                delivered_package: None,
+                repeated_package: None,
+                max_stalled_rounds: 10_000,
+                stalled_rounds: 0,
+                round_start_package: None,
+                log: false,
+                packets: Vec::new(),
+                idle_cycles: 0,
+                deliveries: Vec::new(),
+            },
         })
     }
 
+    #[allow(unused, reason = "tests")]
+    const fn machine_count(&self) -> usize {
+        self.scheduler.machines.len()
+    }
+
     fn run_until_first_nat_package(&mut self) -> Result<Option<(Value, Value)>, RuntimeError> {
-        loop {
-            for machine_ix in 0..self.machines.len() {
-                self.process_machine(machine_ix)?;
-                if let Some(nat_package) = self.nat_package {
-                    return Ok(Some(nat_package));
-                }
-            }
+        self.nat.wake_idle = false;
+        self.scheduler.run(&mut self.nat)?;
+        match self.nat.first_package {
+            Some(package) => Ok(Some(package)),
+            None => Err(RuntimeError::NoProgress(self.nat.stalled_rounds)),
         }
     }
 
     fn run_with_nat(&mut self) -> Result<Option<(Value, Value)>, RuntimeError> {
-        let mut prev_nat_package = None;
-        loop {
-            let mut any_activity = false;
-            for machine_ix in 0..self.machines.len() {
-                any_activity = self.process_machine(machine_ix)? || any_activity;
-            }
-            if !any_activity {
-                if let Some((x, y)) = self.nat_package {
-                    if prev_nat_package == Some((x, y)) {
-                        return Ok(Some((x, y)));
-                    }
-                    prev_nat_package = Some((x, y));
-                    self.send_package(0, x, y);
-                } else {
-                    return Err(RuntimeError::NoNatPackage);
-                }
-            }
+        self.nat.wake_idle = true;
+        self.scheduler.run(&mut self.nat)?;
+        if let Some(package) = self.nat.repeated_package {
+            Ok(Some(package))
+        } else if self.nat.stalled_rounds >= self.nat.max_stalled_rounds {
+            Err(RuntimeError::NoProgress(self.nat.stalled_rounds))
+        } else {
+            Err(RuntimeError::NoNatPackage)
         }
     }
 
-    fn process_machine(&mut self, machine_ix: usize) -> Result<bool, RuntimeError> {
-        let machine = &mut self.machines[machine_ix];
-        if machine.inputs.is_empty() {
-            machine.inputs.push_back(-1);
-        }
-        machine.run_until_input()?;
-        if machine.outputs.len() < 3 {
-            return Ok(false);
+    /// Runs like `run_with_nat`, and returns the `y` of every packet the NAT delivered before the
+    /// first repeat. The last of them is the repeated value.
+    #[allow(unused, reason = "tests")]
+    fn nat_delivery_log(&mut self) -> Result<Vec<Value>, RuntimeError> {
+        self.run_with_nat()?;
+        Ok(self.nat.deliveries.clone())
+    }
+}
+
+impl Nat {
+    fn log_packet(&mut self, src: Value, dest: Value, x: Value, y: Value) {
+        if self.log {
+            self.packets.push((src, dest, x, y));
         }
-        let outputs = machine
-            .outputs
-            .drain(..machine.outputs.len() / 3 * 3)
-            .collect::<Vec<_>>();
-        for ((&dest, &x), &y) in outputs
-            .iter()
-            .zip(&outputs[1..])
-            .zip(&outputs[2..])
-            .step_by(3)
-        {
-            self.send_package(dest, x, y);
+    }
+}
+
+impl Router for Nat {
+    fn packet_size(&self) -> usize {
+        3
+    }
+
+    fn route<'a>(&mut self, src: usize, packet: &'a [Value]) -> Option<(usize, &'a [Value])> {
+        let &[dest, x, y] = packet else {
+            return None;
+        };
+        self.log_packet(Value::try_from(src).unwrap(), dest, x, y);
+        if dest == self.address {
+            self.package = Some((x, y));
+            self.first_package.get_or_insert((x, y));
+            return None;
         }
-        Ok(true)
+        usize::try_from(dest).ok().map(|ix| (ix, &packet[1..]))
     }
 
-    fn send_package(&mut self, dest: Value, x: Value, y: Value) {
-        if dest == 255 {
-            self.nat_package = Some((x, y));
-            return;
+    fn idle_input(&self) -> Option<Value> {
+        Some(-1)
+    }
+
+    fn end_round(&mut self, progress: bool) -> ControlFlow<(), Option<(usize, Vec<Value>)>> {
+        if !progress {
+            self.idle_cycles += 1;
+        }
+        if !self.wake_idle && self.first_package.is_some() {
+            return ControlFlow::Break(());
         }
-        if let Ok(ix) = usize::try_from(dest)
-            && let Some(machine) = self.machines.get_mut(ix)
-        {
-            machine.inputs.push_back(x);
-            machine.inputs.push_back(y);
+        let wake = if self.wake_idle && !progress {
+            let Some((x, y)) = self.package else {
+                return ControlFlow::Break(());
+            };
+            if self.delivered_package == Some((x, y)) {
+                self.repeated_package = Some((x, y));
+                return ControlFlow::Break(());
+            }
+            self.delivered_package = Some((x, y));
+            self.deliveries.push(y);
+            self.log_packet(self.address, 0, x, y);
+            Some((0, vec![x, y]))
+        } else {
+            None
+        };
+        if self.package == self.round_start_package {
+            self.stalled_rounds += 1;
+            if self.stalled_rounds >= self.max_stalled_rounds {
+                return ControlFlow::Break(());
+            }
+        } else {
+            self.stalled_rounds = 0;
         }
+        self.round_start_package = self.package;
+        ControlFlow::Continue(wake)
     }
 }
 
-// No test cases
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends `(address, address)` to 255 on boot. After that, every packet `(x, y)` it receives
+    /// is passed on to 255 as `(x, max(y - 1, 0))`.
+    const TOY: &[Value] = &[
+        3, 100, // in [100]
+        104, 255, 4, 100, 4, 100, // out 255, [100], [100]
+        3, 101, // loop: in [101]
+        1008, 101, -1, 103, // [103] = [101] == -1
+        1005, 103, 8, // jnz [103] loop
+        3, 102, // in [102]
+        1006, 102, 26, // jz [102] send
+        1001, 102, -1, 102, // [102] -= 1
+        104, 255, 4, 101, 4, 102, // send: out 255, [101], [102]
+        1105, 1, 8, // jmp loop
+    ];
+
+    /// Keeps sending packets to itself, so the network is never idle, but never talks to the NAT.
+    const BUSY: &[Value] = &[
+        3, 100, // in [100]
+        4, 100, 104, 0, 104, 0, // out [100], 0, 0
+        3, 101, 3, 102, // loop: in [101], in [102]
+        4, 100, 4, 101, 4, 102, // out [100], [101], [102]
+        1105, 1, 8, // jmp loop
+    ];
+
+    /// Only ever reads its input, and never sends anything.
+    const QUIET: &[Value] = &[
+        3, 100, // in [100]
+        3, 101, // loop: in [101]
+        1105, 1, 2, // jmp loop
+    ];
+
+    #[test]
+    fn test_first_nat_package() {
+        let mut sim = NetworkSimulator::new(TOY, 3, false).unwrap();
+        assert_eq!(sim.machine_count(), 3);
+        assert_eq!(sim.run_until_first_nat_package().unwrap(), Some((0, 0)));
+        assert_eq!(sim.nat.idle_cycles, 0);
+    }
+
+    #[test]
+    fn test_first_nat_package_idle() {
+        let mut sim = NetworkSimulator::new(QUIET, 2, false).unwrap();
+        sim.nat.max_stalled_rounds = 5;
+        assert!(matches!(
+            sim.run_until_first_nat_package(),
+            Err(RuntimeError::NoProgress(5))
+        ));
+        assert_eq!(sim.nat.idle_cycles, 5);
+    }
+
+    #[test]
+    fn test_run_with_nat() {
+        let mut sim = NetworkSimulator::new(TOY, 3, false).unwrap();
+        sim.nat.log = true;
+        assert_eq!(sim.run_with_nat().unwrap(), Some((2, 0)));
+        assert_eq!(sim.nat.idle_cycles, 4);
+        assert_eq!(
+            sim.nat.packets,
+            [
+                (0, 255, 0, 0),
+                (1, 255, 1, 1),
+                (2, 255, 2, 2),
+                (255, 0, 2, 2),
+                (0, 255, 2, 1),
+                (255, 0, 2, 1),
+                (0, 255, 2, 0),
+                (255, 0, 2, 0),
+                (0, 255, 2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nat_delivery_log() {
+        let mut sim = NetworkSimulator::new(TOY, 3, false).unwrap();
+        assert_eq!(sim.nat_delivery_log().unwrap(), [2, 1, 0]);
+    }
+
+    #[test]
+    fn test_no_progress() {
+        let mut sim = NetworkSimulator::new(BUSY, 2, false).unwrap();
+        sim.nat.max_stalled_rounds = 20;
+        assert!(matches!(
+            sim.clone().run_until_first_nat_package(),
+            Err(RuntimeError::NoProgress(20))
+        ));
+        assert!(matches!(
+            sim.run_with_nat(),
+            Err(RuntimeError::NoProgress(20))
+        ));
+    }
+}
@@ -1,12 +1,19 @@
 use std::fmt::{Display, Write};
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 
 use thiserror::Error;
 
+use crate::cycle::find_cycle;
+use crate::solver::{Answer, Solver};
+
 #[derive(Debug, Error)]
 enum ParseError {
     #[error("Invalid tile")]
     InvalidTile,
+    #[error("Grid does not match the expected size")]
+    #[allow(unused, reason = "tests")]
+    InvalidSize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -42,6 +49,10 @@ impl Bugs {
         self.0.count_ones()
     }
 
+    const fn get(self, row: usize, col: usize) -> bool {
+        self.0 & (1 << (5 * row + col)) != 0
+    }
+
     const fn count_outer_right(self) -> u32 {
         (self.0 & 0b10000_10000_10000_10000_10000).count_ones()
     }
@@ -132,7 +143,7 @@ impl Display for Bugs {
                 f.write_char('\n')?;
             }
             for col in 0..5 {
-                let bit = self.0 & (1 << (5 * row + col)) != 0;
+                let bit = self.get(row, col);
                 if (row, col) == (2, 2) && f.alternate() {
                     f.write_char('?')?;
                 } else {
@@ -144,6 +155,104 @@ impl Display for Bugs {
     }
 }
 
+/// Same rules as `Bugs::simple_evolve`, but for a grid of any size. `Bugs` is the fast path for
+/// the 5×5 puzzle grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(unused, reason = "tests")]
+struct BugsN<const W: usize, const H: usize>([[bool; W]; H]);
+
+#[allow(unused, reason = "tests")]
+impl<const W: usize, const H: usize> BugsN<W, H> {
+    fn simple_evolve(&self) -> Self {
+        let mut grid = [[false; W]; H];
+        for (row, line) in grid.iter_mut().enumerate() {
+            for (col, cell) in line.iter_mut().enumerate() {
+                let neighbors = [
+                    row.checked_sub(1).map(|r| (r, col)),
+                    (row + 1 < H).then_some((row + 1, col)),
+                    col.checked_sub(1).map(|c| (row, c)),
+                    (col + 1 < W).then_some((row, col + 1)),
+                ]
+                .into_iter()
+                .flatten()
+                .filter(|&(r, c)| self.0[r][c])
+                .count();
+                *cell = matches!((self.0[row][col], neighbors), (false, 1..=2) | (true, 1));
+            }
+        }
+        Self(grid)
+    }
+
+    /// `None` for grids of more than 64 cells, where the rating does not fit.
+    fn biodiversity(&self) -> Option<u64> {
+        if W * H > 64 {
+            return None;
+        }
+        Some(
+            self.0
+                .iter()
+                .flatten()
+                .enumerate()
+                .filter(|&(_, &bug)| bug)
+                .map(|(ix, _)| 1 << ix)
+                .sum(),
+        )
+    }
+}
+
+impl<const W: usize, const H: usize> FromStr for BugsN<W, H> {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut grid = [[false; W]; H];
+        let mut lines = input.lines();
+        for line in &mut grid {
+            let text = lines.next().ok_or(ParseError::InvalidSize)?;
+            if text.len() != W {
+                return Err(ParseError::InvalidSize);
+            }
+            for (cell, ch) in line.iter_mut().zip(text.bytes()) {
+                *cell = match ch {
+                    b'#' => true,
+                    b'.' => false,
+                    _ => return Err(ParseError::InvalidTile),
+                };
+            }
+        }
+        if lines.next().is_some() {
+            return Err(ParseError::InvalidSize);
+        }
+        Ok(Self(grid))
+    }
+}
+
+impl<const W: usize, const H: usize> Display for BugsN<W, H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (row, line) in self.0.iter().enumerate() {
+            if row > 0 {
+                f.write_char('\n')?;
+            }
+            for &bug in line {
+                f.write_char(if bug { '#' } else { '.' })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rgba")]
+impl<const W: usize, const H: usize> crate::render::Raster for BugsN<W, H> {
+    type Cell = bool;
+
+    fn size(&self) -> (usize, usize) {
+        (W, H)
+    }
+
+    fn cells(&self) -> impl Iterator<Item = bool> {
+        self.0.iter().flatten().copied()
+    }
+}
+
 #[aoc_generator(day24)]
 fn parse(input: &str) -> Result<Bugs, ParseError> {
     input.parse()
@@ -152,43 +261,33 @@ fn parse(input: &str) -> Result<Bugs, ParseError> {
 #[aoc(day24, part1)]
 #[expect(clippy::trivially_copy_pass_by_ref, reason = "aoc lib")]
 fn part_1(bugs: &Bugs) -> u32 {
-    let first_repeat = find_first_repeat(*bugs, Bugs::simple_evolve);
+    let cycle = find_cycle(bugs, |bugs| *bugs = bugs.simple_evolve());
 
-    first_repeat.biodiversity()
+    cycle.first_repeat.biodiversity()
 }
 
-fn find_first_repeat<T: Copy + Eq>(start: T, step: impl Fn(T) -> T) -> T {
-    let mut power = 1;
-    let mut cycle_len = 1;
-    let mut slow = start;
-    let mut fast = step(start);
-    while slow != fast {
-        if power == cycle_len {
-            slow = fast;
-            power *= 2;
-            cycle_len = 0;
-        }
-        fast = step(fast);
-        cycle_len += 1;
-    }
-    slow = start;
-    fast = start;
-    for _ in 0..cycle_len {
-        fast = step(fast);
+#[aoc(day24, part2)]
+#[expect(clippy::trivially_copy_pass_by_ref, reason = "aoc lib")]
+fn part_2(&bugs: &Bugs) -> u32 {
+    bugs_after(bugs, MINUTES)
+}
+
+pub struct Day24;
+
+impl Solver for Day24 {
+    fn part1(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_1(&parsed)).into_answer()
     }
-    // let mut cycle_start = 0;
-    while slow != fast {
-        slow = step(slow);
-        fast = step(fast);
-        // cycle_start += 1;
+
+    fn part2(&self, input: &str) -> String {
+        parse(input).map(|parsed| part_2(&parsed)).into_answer()
     }
-    slow
 }
 
-#[aoc(day24, part2)]
-#[expect(clippy::trivially_copy_pass_by_ref, reason = "aoc lib")]
-fn part_2(&bugs: &Bugs) -> u32 {
-    layered_evolution(bugs, 200).count_all()
+const MINUTES: usize = 200;
+
+fn bugs_after(bugs: Bugs, minutes: usize) -> u32 {
+    layered_evolution(bugs, minutes).count_all()
 }
 
 fn layered_evolution(bugs: Bugs, cycles: usize) -> BugStack {
@@ -237,6 +336,32 @@ impl BugStack {
     fn count_all(&self) -> u32 {
         self.layers.iter().copied().map(Bugs::count_all).sum()
     }
+
+    /// Depths of the outermost and innermost layers. Negative depths are outside the initial
+    /// grid.
+    fn depth_range(&self) -> RangeInclusive<i32> {
+        let len = i32::try_from(self.layers.len()).unwrap();
+        self.numbering_offset..=self.numbering_offset + len - 1
+    }
+
+    /// Whether there is a bug at `(row, col)` on the layer at `depth`. `None` for the center tile,
+    /// which holds the next layer, and for positions outside the stack.
+    #[allow(unused, reason = "tests")]
+    fn get(&self, depth: i32, row: usize, col: usize) -> Option<bool> {
+        if row >= 5 || col >= 5 || (row, col) == (2, 2) {
+            return None;
+        }
+        let ix = usize::try_from(depth - self.numbering_offset).ok()?;
+        Some(self.layers.get(ix)?.get(row, col))
+    }
+
+    /// `(depth, count)` for each layer, outermost first.
+    #[allow(unused, reason = "tests")]
+    fn layer_counts(&self) -> Vec<(i32, u32)> {
+        self.depth_range()
+            .zip(self.layers.iter().copied().map(Bugs::count_all))
+            .collect()
+    }
 }
 
 impl Display for BugStack {
@@ -247,9 +372,8 @@ impl Display for BugStack {
             .iter()
             .map(|bugs| format!("{bugs:#}"))
             .collect::<Vec<_>>();
-        for ix in 0..all.len() {
-            let depth = i32::try_from(ix).unwrap() + self.numbering_offset;
-            write!(f, "Depth {depth:<2}  ",)?;
+        for depth in self.depth_range() {
+            write!(f, "Depth {depth:<2}  ")?;
         }
         writeln!(f)?;
         for line in 0..5 {
@@ -266,6 +390,7 @@ impl Display for BugStack {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
 
     const EXAMPLE: &str = "\
         ....#\n\
@@ -291,6 +416,70 @@ mod tests {
         assert_eq!(bugs.to_string(), expected);
     }
 
+    #[test]
+    fn test_simple_evolve_generic() {
+        let mut bugs = EXAMPLE.parse::<BugsN<5, 5>>().unwrap();
+        for _ in 0..4 {
+            bugs = bugs.simple_evolve();
+        }
+        let expected = "\
+            ####.\n\
+            ....#\n\
+            ##..#\n\
+            .....\n\
+            ##...\
+        ";
+        assert_eq!(bugs.to_string(), expected);
+    }
+
+    #[test]
+    fn test_first_repeat_generic() {
+        let bugs = EXAMPLE.parse::<BugsN<5, 5>>().unwrap();
+        let cycle = find_cycle(&bugs, |bugs| *bugs = bugs.simple_evolve());
+        assert_eq!(cycle.first_repeat.biodiversity(), Some(2_129_920));
+    }
+
+    #[cfg(feature = "rgba")]
+    #[test]
+    fn test_to_rgba() {
+        let bugs = "#..\n..#".parse::<BugsN<3, 2>>().unwrap();
+        let (pixels, width, height) = crate::render::to_rgba(&bugs, |&bug| [u8::from(bug); 4]);
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(
+            pixels,
+            [[1; 4], [0; 4], [0; 4], [0; 4], [0; 4], [1; 4]].concat()
+        );
+    }
+
+    #[test]
+    fn test_biodiversity_too_large() {
+        let mut bugs = BugsN::<8, 8>([[false; 8]; 8]);
+        bugs.0[7][7] = true;
+        assert_eq!(bugs.biodiversity(), Some(1 << 63));
+        assert_eq!(BugsN::<9, 8>([[true; 9]; 8]).biodiversity(), None);
+    }
+
+    #[test]
+    fn test_other_size() {
+        let mut bugs = "#..\n...".parse::<BugsN<3, 2>>().unwrap();
+        bugs = bugs.simple_evolve();
+        assert_eq!(bugs.to_string(), ".#.\n#..");
+        bugs = bugs.simple_evolve();
+        assert_eq!(bugs.to_string(), "#.#\n.#.");
+    }
+
+    #[test]
+    fn test_parse_wrong_size() {
+        assert!(matches!(
+            EXAMPLE.parse::<BugsN<4, 5>>(),
+            Err(ParseError::InvalidSize)
+        ));
+        assert!(matches!(
+            EXAMPLE.parse::<BugsN<5, 6>>(),
+            Err(ParseError::InvalidSize)
+        ));
+    }
+
     #[test]
     fn test_part_1() {
         let bugs = parse(EXAMPLE).unwrap();
@@ -305,4 +494,65 @@ mod tests {
         println!("{result}");
         assert_eq!(result.count_all(), 99);
     }
+
+    #[test]
+    fn test_layer_counts() {
+        let bugs = parse(EXAMPLE).unwrap();
+        let stack = layered_evolution(bugs, 10);
+        assert_eq!(stack.depth_range(), -5..=5);
+        assert_eq!(
+            stack.layer_counts(),
+            [
+                (-5, 7),
+                (-4, 6),
+                (-3, 6),
+                (-2, 10),
+                (-1, 10),
+                (0, 5),
+                (1, 15),
+                (2, 12),
+                (3, 7),
+                (4, 9),
+                (5, 12),
+            ]
+        );
+    }
+
+    #[test_case(-5, "..#..\n.#.#.\n..?.#\n.#.#.\n..#.."; "outermost depth")]
+    #[test_case(0, ".#...\n.#.##\n.#?..\n.....\n....."; "depth 0")]
+    #[test_case(1, ".##..\n#..##\n..?.#\n##.##\n#####"; "depth 1")]
+    #[test_case(5, "####.\n#..#.\n#.?#.\n####.\n....."; "innermost depth")]
+    fn test_get(depth: i32, expected: &str) {
+        let bugs = parse(EXAMPLE).unwrap();
+        let stack = layered_evolution(bugs, 10);
+        let layer = (0..5)
+            .map(|row| {
+                (0..5)
+                    .map(|col| match stack.get(depth, row, col) {
+                        Some(true) => '#',
+                        Some(false) => '.',
+                        None => '?',
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(layer, expected);
+    }
+
+    #[test_case(-6, 0, 0; "above outermost")]
+    #[test_case(6, 0, 0; "below innermost")]
+    #[test_case(0, 5, 0; "row out of range")]
+    #[test_case(0, 0, 5; "column out of range")]
+    fn test_get_out_of_range(depth: i32, row: usize, col: usize) {
+        let bugs = parse(EXAMPLE).unwrap();
+        let stack = layered_evolution(bugs, 10);
+        assert_eq!(stack.get(depth, row, col), None);
+    }
+
+    #[test]
+    fn test_bugs_after() {
+        let bugs = parse(EXAMPLE).unwrap();
+        assert_eq!(bugs_after(bugs, 10), 99);
+    }
 }
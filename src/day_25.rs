@@ -1,7 +1,9 @@
-use std::fmt::{Display, Write};
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::num::ParseIntError;
 
 use crate::machine::{Machine, MachineError, Value, parse_program};
+use crate::solver::{Answer, Solver};
 
 #[aoc_generator(day25)]
 fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
@@ -10,10 +12,36 @@ fn parse(input: &str) -> Result<Vec<Value>, ParseIntError> {
 
 #[aoc(day25, part1)]
 fn part_1(program: &[Value]) -> u64 {
+    part_1_traced(program, false)
+}
+
+fn part_1_traced(program: &[Value], trace: bool) -> u64 {
     let mut mud = DroidMud::new(program);
+    mud.machine.log = trace;
     mud.run().unwrap()
 }
 
+#[derive(Default)]
+pub struct Day25 {
+    trace: bool,
+}
+
+impl Solver for Day25 {
+    fn part1(&self, input: &str) -> String {
+        parse(input)
+            .map(|parsed| part_1_traced(&parsed, self.trace))
+            .into_answer()
+    }
+
+    fn part2(&self, _input: &str) -> String {
+        "Day 25 has no second puzzle".to_string()
+    }
+
+    fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Action<'a> {
     North,
@@ -40,6 +68,103 @@ impl Display for Action<'_> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    const fn opposite(self) -> Self {
+        match self {
+            Self::North => Self::South,
+            Self::East => Self::West,
+            Self::South => Self::North,
+            Self::West => Self::East,
+        }
+    }
+
+    const fn action(self) -> Action<'static> {
+        match self {
+            Self::North => Action::North,
+            Self::East => Action::East,
+            Self::South => Action::South,
+            Self::West => Action::West,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "north" => Self::North,
+            "east" => Self::East,
+            "south" => Self::South,
+            "west" => Self::West,
+            _ => return None,
+        })
+    }
+}
+
+/// More than enough for any single command. A machine still running after this many
+/// instructions is assumed to be stuck.
+const STEP_LIMIT: usize = 10_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Room {
+    name: String,
+    description: String,
+    doors: Vec<Direction>,
+    items: Vec<String>,
+}
+
+/// Reads the last room described in the droid output. Being pushed back from the pressure-sensitive
+/// floor describes two rooms, and the droid ends up in the second one.
+fn parse_room(text: &str) -> Option<Room> {
+    let start = text.rfind("== ")?;
+    let mut lines = text[start..].lines();
+    let name = lines
+        .next()?
+        .strip_prefix("== ")?
+        .strip_suffix(" ==")?
+        .to_string();
+    let description = lines
+        .by_ref()
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut doors = Vec::new();
+    let mut items = Vec::new();
+    let mut section = "";
+    for line in lines {
+        if let Some(entry) = line.strip_prefix("- ") {
+            match section {
+                "Doors here lead:" => doors.push(Direction::from_name(entry)?),
+                "Items here:" => items.push(entry.to_string()),
+                _ => {}
+            }
+        } else {
+            section = line;
+        }
+    }
+    Some(Room {
+        name,
+        description,
+        doors,
+        items,
+    })
+}
+
+/// What the droid found while walking through the ship.
+#[derive(Debug, Default)]
+struct Exploration {
+    rooms: HashMap<String, Room>,
+    inventory: Vec<String>,
+    /// Path from the starting room to the security checkpoint, and the door leading on to the
+    /// pressure-sensitive floor.
+    checkpoint: Option<(Vec<Direction>, Direction)>,
+}
+
 struct DroidMud {
     machine: Machine,
     log: bool,
@@ -58,7 +183,18 @@ impl DroidMud {
             Ok(()) | Err(MachineError::Stopped) => (),
             Err(err) => println!("ERROR: {err}"),
         }
+        self.drain_output()
+    }
 
+    /// Like `get_output`, but `None` if the machine halted or did not ask for more input within
+    /// `STEP_LIMIT` instructions.
+    fn try_get_output(&mut self) -> Option<String> {
+        let waiting = self.machine.run_n_steps(STEP_LIMIT).unwrap_or(false);
+        let text = self.drain_output();
+        waiting.then_some(text)
+    }
+
+    fn drain_output(&mut self) -> String {
         let output = self
             .machine
             .outputs
@@ -84,78 +220,257 @@ impl DroidMud {
         if self.log {
             println!("> {action}");
         }
-        writeln!(&mut self.machine, "{action}").unwrap();
+        self.machine.extend_ascii_line(&action.to_string());
         self.get_output()
     }
 
-    fn run(&mut self) -> Option<u64> {
-        let actions = [
-            Action::East,
-            Action::TakeItem("weather machine"),
-            Action::West,
-            Action::West,
-            //Action::TakeItem("giant electromagnet"),
-            Action::West,
-            Action::TakeItem("bowl of rice"),
-            Action::East,
-            Action::North,
-            Action::TakeItem("polygon"),
-            Action::East,
-            Action::TakeItem("hypercube"),
-            Action::South,
-            Action::TakeItem("dark matter"),
-            Action::West,
-            Action::East,
-            Action::North,
-            Action::West,
-            Action::North,
-            Action::TakeItem("candy cane"),
-            Action::North,
-            //Action::TakeItem("escape pod"),
-            Action::South,
-            Action::West,
-            //Action::TakeItem("molten lava"),
-            Action::North,
-            Action::TakeItem("manifold"),
-            Action::West,
-            //Action::TakeItem("infinite loop"),
-            Action::East,
-            Action::South,
-            Action::West,
-            Action::North,
-            Action::TakeItem("dehydrated water"),
-            Action::West,
-        ];
-        self.get_output();
-        let mut inventory = Vec::new();
-        for action in &actions {
-            self.execute(action);
-            if let Action::TakeItem(item) = action {
-                inventory.push(item);
-            }
+    fn try_execute(&mut self, action: &Action) -> Option<String> {
+        if self.log {
+            println!("> {action}");
         }
+        self.machine.extend_ascii_line(&action.to_string());
+        self.try_get_output()
+    }
 
-        let mut inventory_status = vec![true; inventory.len()];
+    /// Picks up the item, unless that ends the game, hangs the machine, or leaves the droid
+    /// unable to walk through `door`.
+    fn try_take(&mut self, item: &str, door: Direction) -> bool {
+        let snapshot = self.machine.clone();
+        if self.try_execute(&Action::TakeItem(item)).is_some() {
+            let taken = self.machine.clone();
+            let can_move = self
+                .try_execute(&door.action())
+                .as_deref()
+                .and_then(parse_room)
+                .is_some();
+            self.machine = taken;
+            if can_move {
+                return true;
+            }
+        }
+        self.machine = snapshot;
+        false
+    }
 
-        let mut index: u32 = 1;
-        let mut prev_gray_code = 0;
-        let mut output = self.execute(&Action::South);
-        while output.contains("Alert!") {
-            index += 1;
-            let gray_code = index ^ (index >> 1);
-            let toggled_item = (gray_code ^ prev_gray_code).trailing_zeros() as usize;
-            prev_gray_code = gray_code;
+    /// Visits every room reachable from the start, picking up all items that are safe to carry,
+    /// and returns to the starting room.
+    fn explore(&mut self) -> Exploration {
+        let mut exploration = Exploration::default();
+        if let Some(room) = parse_room(&self.get_output()) {
+            self.explore_room(room, &mut Vec::new(), &mut exploration);
+        }
+        exploration
+    }
 
-            if inventory_status[toggled_item] {
-                self.execute(&Action::DropItem(inventory[toggled_item]));
-            } else {
-                self.execute(&Action::TakeItem(inventory[toggled_item]));
+    fn explore_room(
+        &mut self,
+        room: Room,
+        path: &mut Vec<Direction>,
+        exploration: &mut Exploration,
+    ) {
+        if let Some(&door) = room.doors.first() {
+            for item in &room.items {
+                if self.try_take(item, door) {
+                    exploration.inventory.push(item.clone());
+                }
+            }
+        }
+        let name = room.name.clone();
+        let doors = room.doors.clone();
+        exploration.rooms.insert(room.name.clone(), room);
+        for door in doors {
+            if path.last() == Some(&door.opposite()) {
+                continue;
             }
-            inventory_status[toggled_item] ^= true;
-            output = self.execute(&Action::South);
+            let snapshot = self.machine.clone();
+            let Some(next) = parse_room(&self.execute(&door.action())) else {
+                self.machine = snapshot;
+                continue;
+            };
+            if next.name == name {
+                // Pushed back by the security checkpoint.
+                exploration.checkpoint = Some((path.clone(), door));
+                self.machine = snapshot;
+                continue;
+            }
+            if !exploration.rooms.contains_key(&next.name) {
+                path.push(door);
+                self.explore_room(next, path, exploration);
+                path.pop();
+            }
+            self.execute(&door.opposite().action());
         }
+    }
+
+    fn run(&mut self) -> Option<u64> {
+        let exploration = self.explore();
+        let (path, floor_door) = exploration.checkpoint?;
+        for door in path {
+            self.execute(&door.action());
+        }
+        let items = exploration
+            .inventory
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let (_, output) = self.pass_checkpoint(floor_door, &items)?;
         output
             .split_ascii_whitespace()
             .find_map(|word| word.parse::<u64>().ok())
     }
+
+    /// Which of the carried `items` let the droid through `door` onto the pressure-sensitive
+    /// floor.
+    #[allow(unused, reason = "tests")]
+    fn find_passing_subset<'a>(
+        &mut self,
+        door: Direction,
+        items: &[&'a str],
+    ) -> Option<Vec<&'a str>> {
+        self.pass_checkpoint(door, items)
+            .map(|(passing, _)| passing)
+    }
+
+    /// Tries every subset of `items`, changing one item at a time, until the floor lets the droid
+    /// through. The droid must start out carrying all of them.
+    fn pass_checkpoint<'a>(
+        &mut self,
+        door: Direction,
+        items: &[&'a str],
+    ) -> Option<(Vec<&'a str>, String)> {
+        let mut carried = vec![true; items.len()];
+        let mut toggles = gray_code_toggles(items.len());
+        loop {
+            let output = self.execute(&door.action());
+            if !is_rejected(&output) {
+                let passing = items
+                    .iter()
+                    .zip(&carried)
+                    .filter(|&(_, &carried)| carried)
+                    .map(|(&item, _)| item)
+                    .collect();
+                return Some((passing, output));
+            }
+            let item_ix = toggles.next()?;
+            if carried[item_ix] {
+                self.execute(&Action::DropItem(items[item_ix]));
+            } else {
+                self.execute(&Action::TakeItem(items[item_ix]));
+            }
+            carried[item_ix] ^= true;
+        }
+    }
+}
+
+/// The item to pick up or drop at each step of a Gray code walk through all subsets of `count`
+/// items.
+fn gray_code_toggles(count: usize) -> impl Iterator<Item = usize> {
+    (1_u64..1 << count).map(|index| index.trailing_zeros() as usize)
+}
+
+fn is_rejected(output: &str) -> bool {
+    output.contains("Alert!")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HULL_BREACH: &str = "\
+        == Hull Breach ==\n\
+        You got in through a hole in the floor here. To keep your ship from also freezing, the hole has been sealed.\n\
+        \n\
+        Doors here lead:\n\
+        - east\n\
+        - south\n\
+        - west\n\
+        \n\
+        Command?\
+    ";
+
+    const EJECTED: &str = "\
+        == Pressure-Sensitive Floor ==\n\
+        Analyzing...\n\
+        \n\
+        Doors here lead:\n\
+        - north\n\
+        \n\
+        A loud, robotic voice says \"Alert! Droids on this ship are lighter than the detected value!\" and you are ejected back to the checkpoint.\n\
+        \n\
+        \n\
+        \n\
+        == Security Checkpoint ==\n\
+        In the next room, a pressure-sensitive floor will verify your identity.\n\
+        \n\
+        Doors here lead:\n\
+        - south\n\
+        - west\n\
+        \n\
+        Items here:\n\
+        - space law space brochure\n\
+        - weather machine\n\
+        \n\
+        Command?\
+    ";
+
+    #[test]
+    fn test_gray_code_toggles() {
+        let toggles = gray_code_toggles(3).collect::<Vec<_>>();
+        assert_eq!(toggles, [0, 1, 0, 2, 0, 1, 0]);
+
+        let mut subsets = vec![0_u8];
+        for toggle in gray_code_toggles(4) {
+            subsets.push(subsets.last().unwrap() ^ (1 << toggle));
+        }
+        subsets.sort_unstable();
+        assert_eq!(subsets, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_is_rejected() {
+        assert!(is_rejected(EJECTED));
+        assert!(!is_rejected(
+            "== Pressure-Sensitive Floor ==\n\
+            Analyzing...\n\
+            \n\
+            Doors here lead:\n\
+            - north\n\
+            \n\
+            A loud, robotic voice says \"Analysis complete! You may proceed.\" and you enter the cockpit.\n\
+            Santa notices your small droid, looks puzzled for a moment, realizes what has happened, and radios your ship directly.\n\
+            \"Oh, hello! You should be able to get in by typing 12345 on the keypad at the main airlock.\""
+        ));
+    }
+
+    #[test]
+    fn test_parse_room() {
+        let room = parse_room(HULL_BREACH).unwrap();
+        assert_eq!(
+            room,
+            Room {
+                name: "Hull Breach".into(),
+                description: "You got in through a hole in the floor here. To keep your ship from \
+                    also freezing, the hole has been sealed."
+                    .into(),
+                doors: vec![Direction::East, Direction::South, Direction::West],
+                items: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_room_after_ejection() {
+        let room = parse_room(EJECTED).unwrap();
+        assert_eq!(room.name, "Security Checkpoint");
+        assert_eq!(room.doors, [Direction::South, Direction::West]);
+        assert_eq!(room.items, ["space law space brochure", "weather machine"]);
+    }
+
+    #[test]
+    fn test_parse_room_without_room() {
+        assert_eq!(
+            parse_room("You take the weather machine.\n\nCommand?"),
+            None
+        );
+    }
 }
@@ -0,0 +1,384 @@
+//! Geometry types shared between days.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::num::ParseIntError;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Position {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Position {
+    pub const fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    /// Manhattan distance to the origin.
+    pub const fn magnitude(self) -> u64 {
+        self.manhattan(Self::new(0, 0))
+    }
+
+    pub const fn manhattan(self, other: Self) -> u64 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    /// Number of king moves needed to reach `other`.
+    #[allow(unused, reason = "tests")]
+    pub const fn chebyshev(self, other: Self) -> u64 {
+        let dx = self.x.abs_diff(other.x);
+        let dy = self.y.abs_diff(other.y);
+        if dx > dy { dx } else { dy }
+    }
+}
+
+impl From<(i64, i64)> for Position {
+    fn from((x, y): (i64, i64)) -> Self {
+        Self { x, y }
+    }
+}
+
+/// Cells on the straight line from `from` to `to`, both included, using Bresenham's algorithm.
+/// Each cell touches the previous one, possibly diagonally.
+#[allow(unused, reason = "tests")]
+pub fn line(from: Position, to: Position) -> impl Iterator<Item = Position> {
+    // Always walk the same way, so that both directions pick the same cells on ties.
+    let swap = (to.x, to.y) < (from.x, from.y);
+    let (start, end) = if swap { (to, from) } else { (from, to) };
+    let dx = start.x.abs_diff(end.x).cast_signed();
+    let dy = -start.y.abs_diff(end.y).cast_signed();
+    let step_x = if start.x < end.x { 1 } else { -1 };
+    let step_y = if start.y < end.y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut pos = start;
+    let mut cells = vec![pos];
+    while pos != end {
+        let error2 = 2 * error;
+        if error2 >= dy {
+            error += dy;
+            pos.x += step_x;
+        }
+        if error2 <= dx {
+            error += dx;
+            pos.y += step_y;
+        }
+        cells.push(pos);
+    }
+    if swap {
+        cells.reverse();
+    }
+    cells.into_iter()
+}
+
+/// A grid where only a few cells are set. Every other cell holds `T::default()`.
+#[derive(Debug, Clone)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Position, T>,
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        Self {
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Default + Clone> SparseGrid<T> {
+    #[allow(unused, reason = "tests")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, pos: Position) -> T {
+        self.cells.get(&pos).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, pos: Position, value: T) {
+        self.cells.insert(pos, value);
+    }
+
+    /// Number of cells that have been set, including those set to the default.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Position, &T)> {
+        self.cells.iter().map(|(&pos, value)| (pos, value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.values()
+    }
+
+    pub fn map<U>(&self, f: impl Fn(&T) -> U) -> SparseGrid<U> {
+        SparseGrid {
+            cells: self
+                .cells
+                .iter()
+                .map(|(&pos, value)| (pos, f(value)))
+                .collect(),
+        }
+    }
+
+    /// Top-left and bottom-right corners of the cells that have been set, or `None` if there are
+    /// none.
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        let mut positions = self.cells.keys();
+        let &first = positions.next()?;
+        Some(positions.fold((first, first), |(min, max), pos| {
+            (
+                Position::new(min.x.min(pos.x), min.y.min(pos.y)),
+                Position::new(max.x.max(pos.x), max.y.max(pos.y)),
+            )
+        }))
+    }
+
+    /// One line of text per row within `bounds`.
+    #[allow(unused, reason = "debugging")]
+    pub fn render(&self, cell: impl Fn(&T) -> char) -> String {
+        let Some((min, max)) = self.bounds() else {
+            return String::new();
+        };
+        (min.y..=max.y)
+            .map(|y| {
+                (min.x..=max.x)
+                    .map(|x| cell(&self.get(Position::new(x, y))))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseVectorError {
+    #[error("Syntax error")]
+    SyntaxError,
+    #[error(transparent)]
+    InvalidNumber(#[from] ParseIntError),
+}
+
+/// Integer vector in three dimensions, written as `<x=1, y=2, z=3>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Vec3 {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl Vec3 {
+    pub const fn new(x: i64, y: i64, z: i64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Each component replaced by its sign.
+    pub const fn normalized(mut self) -> Self {
+        self.x = self.x.signum();
+        self.y = self.y.signum();
+        self.z = self.z.signum();
+        self
+    }
+
+    /// Manhattan distance to the origin.
+    pub const fn size(self) -> u64 {
+        self.x.unsigned_abs() + self.y.unsigned_abs() + self.z.unsigned_abs()
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+        self.z += rhs.z;
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+        self.z -= rhs.z;
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl MulAssign<i64> for Vec3 {
+    fn mul_assign(&mut self, rhs: i64) {
+        self.x *= rhs;
+        self.y *= rhs;
+        self.z *= rhs;
+    }
+}
+
+impl Mul<i64> for Vec3 {
+    type Output = Self;
+
+    fn mul(mut self, rhs: i64) -> Self::Output {
+        self *= rhs;
+        self
+    }
+}
+
+impl FromStr for Vec3 {
+    type Err = ParseVectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, rest) = s
+            .strip_prefix("<x=")
+            .ok_or(ParseVectorError::SyntaxError)?
+            .split_once(", y=")
+            .ok_or(ParseVectorError::SyntaxError)?;
+        let x = x.parse()?;
+        let (y, rest) = rest
+            .split_once(", z=")
+            .ok_or(ParseVectorError::SyntaxError)?;
+        let y = y.parse()?;
+        let z = rest
+            .strip_suffix(">")
+            .ok_or(ParseVectorError::SyntaxError)?
+            .parse()?;
+        Ok(Self::new(x, y, z))
+    }
+}
+
+impl Display for Vec3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let &Self { x, y, z } = self;
+        write!(f, "<x={x:2}, y={y:2}, z={z:2}>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> SparseGrid<bool> {
+        let mut grid = SparseGrid::new();
+        grid.set(Position::new(-1, 2), true);
+        grid.set(Position::new(1, 3), false);
+        grid.set(Position::new(0, 4), true);
+        grid
+    }
+
+    #[test]
+    fn test_distances() {
+        let a = Position::new(1, -2);
+        let b = Position::new(-3, 4);
+        assert_eq!(a.manhattan(b), 10);
+        assert_eq!(b.manhattan(a), 10);
+        assert_eq!(a.chebyshev(b), 6);
+        assert_eq!(a.magnitude(), 3);
+        assert_eq!(b.magnitude(), 7);
+    }
+
+    #[test]
+    fn test_get_default() {
+        let grid = example();
+        assert!(grid.get(Position::new(-1, 2)));
+        assert!(!grid.get(Position::new(1, 3)));
+        assert!(!grid.get(Position::new(100, 100)));
+        assert_eq!(grid.len(), 3);
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(SparseGrid::<bool>::new().bounds(), None);
+        assert_eq!(
+            example().bounds(),
+            Some((Position::new(-1, 2), Position::new(1, 4)))
+        );
+    }
+
+    fn line_cells(from: (i64, i64), to: (i64, i64)) -> Vec<(i64, i64)> {
+        line(from.into(), to.into())
+            .map(|pos| (pos.x, pos.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_line_straight() {
+        assert_eq!(line_cells((0, 0), (3, 0)), [(0, 0), (1, 0), (2, 0), (3, 0)]);
+        assert_eq!(line_cells((2, 5), (2, 3)), [(2, 5), (2, 4), (2, 3)]);
+        assert_eq!(line_cells((1, 1), (1, 1)), [(1, 1)]);
+    }
+
+    #[test]
+    fn test_line_diagonal() {
+        assert_eq!(line_cells((0, 0), (3, 3)), [(0, 0), (1, 1), (2, 2), (3, 3)]);
+        assert_eq!(line_cells((0, 0), (-2, 2)), [(0, 0), (-1, 1), (-2, 2)]);
+        assert_eq!(
+            line_cells((0, 0), (4, 2)),
+            [(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]
+        );
+    }
+
+    #[test]
+    fn test_line_symmetric() {
+        let ends = (-3..=3).flat_map(|x| (-3..=3).map(move |y| (x, y)));
+        for a in ends.clone() {
+            for b in ends.clone() {
+                let forward = line_cells(a, b);
+                let mut backward = line_cells(b, a);
+                backward.reverse();
+                assert_eq!(forward, backward);
+                let len = a.0.abs_diff(b.0).max(a.1.abs_diff(b.1)) + 1;
+                assert_eq!(forward.len(), usize::try_from(len).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_vec3_parse() {
+        let vector = "<x=-1, y=0, z=12>".parse::<Vec3>().unwrap();
+        assert_eq!(vector, Vec3::new(-1, 0, 12));
+        assert_eq!(vector.to_string(), "<x=-1, y= 0, z=12>");
+        assert!(matches!(
+            "<x=1, y=2>".parse::<Vec3>(),
+            Err(ParseVectorError::SyntaxError)
+        ));
+        assert!(matches!(
+            "<x=1, y=two, z=3>".parse::<Vec3>(),
+            Err(ParseVectorError::InvalidNumber(_))
+        ));
+    }
+
+    #[test]
+    fn test_vec3_ops() {
+        let a = Vec3::new(1, -2, 3);
+        let b = Vec3::new(-4, 5, 0);
+        assert_eq!(a + b, Vec3::new(-3, 3, 3));
+        assert_eq!(a - b, Vec3::new(5, -7, 3));
+        assert_eq!(a * 3, Vec3::new(3, -6, 9));
+        assert_eq!(b.normalized(), Vec3::new(-1, 1, 0));
+        assert_eq!(a.size(), 6);
+    }
+
+    #[test]
+    fn test_render() {
+        let text = example().render(|&bug| if bug { '#' } else { '.' });
+        assert_eq!(text, "#..\n...\n.#.");
+    }
+}
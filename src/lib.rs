@@ -31,6 +31,15 @@ mod day_23;
 mod day_24;
 mod day_25;
 
+mod cycle;
+mod geom;
 mod machine;
+mod render;
+pub mod runner;
+pub mod solver;
+
+pub use day_04::{PasswordEnumerator, PasswordRange, valid_part_2_iter};
+#[cfg(feature = "rgba")]
+pub use render::{Raster, to_rgba};
 
 aoc_lib! { year = 2019 }
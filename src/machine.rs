@@ -1,6 +1,10 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::fmt::{Display, Write};
+use std::fs;
+use std::io;
 use std::num::ParseIntError;
+use std::ops::ControlFlow;
+use std::path::Path;
 
 use thiserror::Error;
 
@@ -257,15 +261,29 @@ pub enum State {
     Stopped,
 }
 
+/// Why the last run of a machine came to an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// Executed a halt instruction.
+    Halted,
+    /// Needs more input to continue.
+    BlockedOnInput,
+}
+
 #[derive(Debug, Clone)]
 pub struct Machine {
     memory: Vec<Value>,
     ip: Value,
     state: State,
+    /// Log every executed instruction to stderr.
     pub log: bool,
+    /// Record the address of every executed instruction in `coverage`.
+    pub track_coverage: bool,
     pub inputs: VecDeque<Value>,
     pub outputs: VecDeque<Value>,
     relative_base: Value,
+    halt_reason: Option<HaltReason>,
+    coverage: HashSet<Value>,
 }
 
 impl Machine {
@@ -278,13 +296,54 @@ impl Machine {
             inputs: VecDeque::new(),
             outputs: VecDeque::new(),
             relative_base: 0,
+            halt_reason: None,
+            track_coverage: false,
+            coverage: HashSet::new(),
         }
     }
 
+    /// Turns logging of executed instructions on or off, for use while building a machine.
+    #[must_use]
+    pub const fn with_log(mut self, log: bool) -> Self {
+        self.log = log;
+        self
+    }
+
     pub const fn state(&self) -> State {
         self.state
     }
 
+    /// Why the last instruction could not continue, or `None` if the machine can keep running.
+    pub const fn halt_reason(&self) -> Option<HaltReason> {
+        self.halt_reason
+    }
+
+    /// Addresses of the instructions executed while `track_coverage` was set.
+    #[allow(unused, reason = "disassembly")]
+    pub const fn coverage(&self) -> &HashSet<Value> {
+        &self.coverage
+    }
+
+    /// Address of the next instruction to execute.
+    #[allow(unused, reason = "tests")]
+    pub const fn ip(&self) -> Value {
+        self.ip
+    }
+
+    /// Continues execution from `ip`. A machine that has stopped can not be moved.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MachineError::Stopped` if the machine is not running.
+    #[allow(unused, reason = "tests")]
+    pub const fn set_ip(&mut self, ip: Value) -> Result<(), MachineError> {
+        if !matches!(self.state, State::Running) {
+            return Err(MachineError::Stopped);
+        }
+        self.ip = ip;
+        Ok(())
+    }
+
     fn get_arg(&self, offset: Value, mode: ParameterMode) -> ArgumentBy {
         let value = self.read(self.ip + offset);
         match mode {
@@ -334,6 +393,20 @@ impl Machine {
         self.state = State::Running;
         self.inputs.clear();
         self.outputs.clear();
+        self.relative_base = 0;
+        self.halt_reason = None;
+        self.coverage.clear();
+    }
+
+    /// Queues the bytes of `s` as input.
+    pub fn extend_ascii(&mut self, s: &str) {
+        self.inputs.extend(s.bytes().map(Value::from));
+    }
+
+    /// Queues the bytes of `s` as input, followed by a newline.
+    pub fn extend_ascii_line(&mut self, s: &str) {
+        self.extend_ascii(s);
+        self.inputs.push_back(Value::from(b'\n'));
     }
 
     fn read_input(&mut self) -> Result<Value, MachineError> {
@@ -345,6 +418,20 @@ impl Machine {
     }
 
     fn step(&mut self) -> Result<(), MachineError> {
+        let ip = self.ip;
+        let result = self.execute_next();
+        if self.track_coverage && result.is_ok() {
+            self.coverage.insert(ip);
+        }
+        self.halt_reason = match (&result, self.state) {
+            (_, State::Stopped) => Some(HaltReason::Halted),
+            (Err(MachineError::EmptyInput), State::Running) => Some(HaltReason::BlockedOnInput),
+            _ => None,
+        };
+        result
+    }
+
+    fn execute_next(&mut self) -> Result<(), MachineError> {
         if self.state != State::Running {
             return Err(MachineError::Stopped);
         }
@@ -352,14 +439,14 @@ impl Machine {
         match op {
             OpCode::Nonary(op) => {
                 if self.log {
-                    println!("[{}] {op:?}", self.ip);
+                    eprintln!("[{}] {op:?}", self.ip);
                 }
                 self.ip = op.execute(self)?.unwrap_or(self.ip + 1);
             }
             OpCode::Unary(op, p1) => {
                 let arg1 = self.get_arg(1, p1);
                 if self.log {
-                    println!("[{}] {op:?} {arg1}", self.ip);
+                    eprintln!("[{}] {op:?} {arg1}", self.ip);
                 }
                 self.ip = op.execute(arg1, self)?.unwrap_or(self.ip + 2);
             }
@@ -367,7 +454,7 @@ impl Machine {
                 let arg1 = self.get_arg(1, p1);
                 let arg2 = self.get_arg(2, p2);
                 if self.log {
-                    println!("[{}] {op:?} {arg1} {arg2}", self.ip);
+                    eprintln!("[{}] {op:?} {arg1} {arg2}", self.ip);
                 }
                 self.ip = op.execute(arg1, arg2, self)?.unwrap_or(self.ip + 3);
             }
@@ -376,7 +463,7 @@ impl Machine {
                 let arg2 = self.get_arg(2, p2);
                 let arg3 = self.get_arg(3, p3);
                 if self.log {
-                    println!("[{}] {op:?} {arg1} {arg2} {arg3}", self.ip);
+                    eprintln!("[{}] {op:?} {arg1} {arg2} {arg3}", self.ip);
                 }
                 self.ip = op.execute(arg1, arg2, arg3, self)?.unwrap_or(self.ip + 4);
             }
@@ -408,19 +495,533 @@ impl Machine {
         }
     }
 
-    #[allow(unused, reason = "tests")]
+    /// Like `run_until_input`, but gives up after `steps` instructions. Returns `false` if the
+    /// machine was still running when the limit was reached.
+    pub fn run_n_steps(&mut self, steps: usize) -> Result<bool, MachineError> {
+        for _ in 0..steps {
+            match self.step() {
+                Ok(()) => (),
+                Err(MachineError::EmptyInput) => return Ok(true),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(false)
+    }
+
+    /// Writes the memory to `path` as comma separated values, in the same format as the puzzle
+    /// input. Only the memory is saved, not the instruction pointer or the queues.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from writing the file.
+    #[allow(unused, reason = "checkpoints")]
+    pub fn save_memory(&self, path: &Path) -> io::Result<()> {
+        let text = self
+            .memory
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        fs::write(path, text)
+    }
+
+    /// Replaces the memory with the values saved in `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from reading the file, or `InvalidData` if it does not hold a program.
+    #[allow(unused, reason = "checkpoints")]
+    pub fn load_memory(&mut self, path: &Path) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        self.memory =
+            parse_program(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(())
+    }
+
     pub fn into_memory(self) -> Vec<Value> {
         self.memory
     }
 }
 
+/// Machines are equal when they would behave the same from here on. Tracing is not part of the
+/// state, and memory that has only grown by zeros reads the same as before.
+impl PartialEq for Machine {
+    fn eq(&self, other: &Self) -> bool {
+        fn used(memory: &[Value]) -> &[Value] {
+            let len = memory
+                .iter()
+                .rposition(|&value| value != 0)
+                .map_or(0, |ix| ix + 1);
+            &memory[..len]
+        }
+        used(&self.memory) == used(&other.memory)
+            && self.ip == other.ip
+            && self.state == other.state
+            && self.relative_base == other.relative_base
+            && self.inputs == other.inputs
+            && self.outputs == other.outputs
+    }
+}
+
+impl Eq for Machine {}
+
 impl Write for Machine {
     fn write_str(&mut self, s: &str) -> std::fmt::Result {
-        self.inputs.extend(s.bytes().map(Value::from));
+        self.extend_ascii(s);
         Ok(())
     }
 }
 
+/// Decides where the outputs of the machines in a `Scheduler` go.
+pub trait Router {
+    /// Number of outputs that make up one packet. A machine's outputs are held back until it has
+    /// written a whole packet.
+    fn packet_size(&self) -> usize {
+        1
+    }
+
+    /// Picks the machine to pass `packet` on to, given the index of the machine that produced
+    /// it, and the part of the packet to queue as its input. `None`, or a machine that does not
+    /// exist, drops the packet.
+    fn route<'a>(&mut self, src: usize, packet: &'a [Value]) -> Option<(usize, &'a [Value])>;
+
+    /// Input given to a machine that is waiting for input with nothing queued. `None` leaves the
+    /// machine blocked until something is routed to it.
+    fn idle_input(&self) -> Option<Value> {
+        None
+    }
+
+    /// Called after every round, with whether any machine made progress in it. Can hand the
+    /// network a packet to queue at a machine, or end the run with `Break`. By default the run
+    /// ends once the network is idle.
+    fn end_round(&mut self, progress: bool) -> ControlFlow<(), Option<(usize, Vec<Value>)>> {
+        if progress {
+            ControlFlow::Continue(None)
+        } else {
+            ControlFlow::Break(())
+        }
+    }
+}
+
+/// Routes single values, with a closure that picks the next machine.
+impl<F: FnMut(usize, Value) -> Option<usize>> Router for F {
+    fn route<'a>(&mut self, src: usize, packet: &'a [Value]) -> Option<(usize, &'a [Value])> {
+        self(src, packet[0]).map(|dest| (dest, packet))
+    }
+}
+
+/// Runs several machines together, passing the outputs of each machine on as inputs to others.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    pub machines: Vec<Machine>,
+}
+
+impl Scheduler {
+    pub const fn new(machines: Vec<Machine>) -> Self {
+        Self { machines }
+    }
+
+    /// Runs rounds of `run_round` until every machine has halted, or `router` ends the run.
+    ///
+    /// Returns `HaltReason::Halted` once every machine has halted, or
+    /// `HaltReason::BlockedOnInput` if the router ended the run before that.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from running the machines, other than running out of input.
+    pub fn run(&mut self, router: &mut impl Router) -> Result<HaltReason, MachineError> {
+        loop {
+            let progress = self.run_round(router)?;
+            if self
+                .machines
+                .iter()
+                .all(|machine| machine.state() == State::Stopped)
+            {
+                return Ok(HaltReason::Halted);
+            }
+            match router.end_round(progress) {
+                ControlFlow::Break(()) => return Ok(HaltReason::BlockedOnInput),
+                ControlFlow::Continue(Some((dest, packet))) => {
+                    if let Some(machine) = self.machines.get_mut(dest) {
+                        machine.inputs.extend(packet);
+                    }
+                }
+                ControlFlow::Continue(None) => {}
+            }
+        }
+    }
+
+    /// Runs each machine in turn until it needs more input, and hands every whole packet it wrote
+    /// to `router`. Returns whether any machine made progress, either by reading queued input or
+    /// by writing a packet. Reading the router's idle input does not count.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from running the machines, other than running out of input.
+    pub fn run_round(&mut self, router: &mut impl Router) -> Result<bool, MachineError> {
+        let packet_size = router.packet_size();
+        let mut progress = false;
+        for ix in 0..self.machines.len() {
+            let machine = &mut self.machines[ix];
+            let blocked = machine.halt_reason() == Some(HaltReason::BlockedOnInput)
+                && machine.inputs.is_empty();
+            if machine.state() != State::Running {
+                continue;
+            }
+            if blocked {
+                let Some(value) = router.idle_input() else {
+                    continue;
+                };
+                machine.inputs.push_back(value);
+            } else {
+                progress = true;
+            }
+            match machine.run_until_input() {
+                Ok(()) | Err(MachineError::Stopped) => {}
+                Err(err) => return Err(err),
+            }
+            let whole = machine.outputs.len() / packet_size * packet_size;
+            let outputs = machine.outputs.drain(..whole).collect::<Vec<_>>();
+            for packet in outputs.chunks_exact(packet_size) {
+                progress = true;
+                if let Some((dest, values)) = router.route(ix, packet)
+                    && let Some(machine) = self.machines.get_mut(dest)
+                {
+                    machine.inputs.extend(values);
+                }
+            }
+        }
+        Ok(progress)
+    }
+}
+
+/// Runs `machine` with `inputs` queued, and returns every output it produced.
+pub fn run_collecting(mut machine: Machine, inputs: &[Value]) -> Result<Vec<Value>, MachineError> {
+    machine.inputs.extend(inputs);
+    machine.run_until_stopped()?;
+    Ok(machine.outputs.into())
+}
+
+/// Addresses of every instruction that can be reached from the start of `program`, found by
+/// decoding without running it. Both branches of a jump are followed, unless the condition is an
+/// immediate value. Jumps to a target that is not an immediate value can not be followed, so code
+/// only reached that way is missed. Addresses that are never reached are likely data.
+#[allow(unused, reason = "disassembly")]
+pub fn reachable_addresses(program: &[Value]) -> HashSet<usize> {
+    let mut reachable = HashSet::new();
+    let mut pending = vec![0];
+    while let Some(address) = pending.pop() {
+        let Some(&value) = program.get(address) else {
+            continue;
+        };
+        let Ok(op) = OpCode::try_from(value) else {
+            continue;
+        };
+        if !reachable.insert(address) {
+            continue;
+        }
+        let immediate = |offset: usize| program.get(address + offset).copied();
+        let fallthrough = match op {
+            OpCode::Nonary(OpCode0::Halt) => None,
+            OpCode::Unary(..) => Some(address + 2),
+            OpCode::Trinary(..) => Some(address + 4),
+            OpCode::Binary(jump, mode1, mode2) => {
+                let jumps_if = jump == OpCode2::JumpIfTrue;
+                let condition =
+                    (mode1 == ParameterMode::Immediate).then(|| immediate(1).unwrap_or(0) != 0);
+                if condition.is_none_or(|cond| cond == jumps_if)
+                    && mode2 == ParameterMode::Immediate
+                    && let Some(Ok(target)) = immediate(2).map(usize::try_from)
+                {
+                    pending.push(target);
+                }
+                condition
+                    .is_none_or(|cond| cond != jumps_if)
+                    .then_some(address + 3)
+            }
+        };
+        pending.extend(fallthrough);
+    }
+    reachable
+}
+
+/// Parses comma separated values. Whitespace around each value is ignored, as is anything after
+/// `//` on a line, and empty entries are skipped, so trailing commas and newlines are allowed.
 pub fn parse_program(input: &str) -> Result<Vec<Value>, ParseIntError> {
-    input.split(',').map(str::parse).collect()
+    input
+        .lines()
+        .map(|line| line.split_once("//").map_or(line, |(code, _)| code))
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::{env, process};
+
+    use super::*;
+    use test_case::test_case;
+
+    /// A path in the temporary directory, unique to this test process.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("aoc-2019-machine-{}-{name}.txt", process::id()))
+    }
+
+    #[test_case("1,0,0,3,99" => vec![1, 0, 0, 3, 99]; "plain")]
+    #[test_case("1,0,0,3,99\n" => vec![1, 0, 0, 3, 99]; "trailing newline")]
+    #[test_case(" 1, 0,0 ,\t3,\n99 " => vec![1, 0, 0, 3, 99]; "spaced")]
+    #[test_case("1,-2,\n3,\n" => vec![1, -2, 3]; "trailing commas")]
+    #[test_case("// add\n1,0,0,3, // store\n99 // halt\n" => vec![1, 0, 0, 3, 99]; "comments")]
+    #[test_case("" => Vec::<Value>::new(); "empty")]
+    fn test_parse_program(input: &str) -> Vec<Value> {
+        parse_program(input).unwrap()
+    }
+
+    #[test]
+    fn test_machine_eq() {
+        // Reads a value, doubles it, and writes it out
+        let program = parse_program("3,9,1002,9,2,9,4,9,99,0").unwrap();
+        let mut machine = Machine::new(&program);
+        let snapshot = machine.clone();
+        machine.inputs.push_back(21);
+        assert_ne!(machine, snapshot);
+        machine.run_until_stopped().unwrap();
+        assert_eq!(machine.outputs, [42]);
+
+        let mut restored = snapshot;
+        restored.log = !restored.log;
+        restored.inputs.push_back(21);
+        restored.run_until_stopped().unwrap();
+        assert_eq!(restored, machine);
+    }
+
+    #[test]
+    fn test_machine_eq_ignores_zero_growth() {
+        let program = parse_program("99").unwrap();
+        let machine = Machine::new(&program);
+        let mut grown = machine.clone();
+        grown.write(10, 0);
+        assert_eq!(grown, machine);
+        grown.write(5, 1);
+        assert_ne!(grown, machine);
+    }
+
+    #[test]
+    fn test_set_ip() {
+        // Outputs 1 and halts, or outputs 2 and halts when started at address 3
+        let program = parse_program("104,1,99,104,2,99").unwrap();
+        let mut machine = Machine::new(&program);
+        assert_eq!(machine.ip(), 0);
+        machine.set_ip(3).unwrap();
+        assert_eq!(machine.run_until_output().unwrap(), Some(2));
+        assert_eq!(machine.ip(), 5);
+        machine.run_until_stopped().unwrap();
+        assert_eq!(machine.ip(), 6);
+        assert!(matches!(machine.set_ip(0), Err(MachineError::Stopped)));
+        assert_eq!(machine.ip(), 6);
+    }
+
+    #[test]
+    fn test_halt_reason() {
+        // Echoes one input and halts
+        let program = parse_program("3,7,4,7,99").unwrap();
+        let mut machine = Machine::new(&program);
+        assert_eq!(machine.halt_reason(), None);
+        assert!(matches!(
+            machine.run_until_stopped(),
+            Err(MachineError::EmptyInput)
+        ));
+        assert_eq!(machine.halt_reason(), Some(HaltReason::BlockedOnInput));
+        machine.inputs.push_back(5);
+        assert_eq!(machine.run_until_output().unwrap(), Some(5));
+        assert_eq!(machine.halt_reason(), None);
+        machine.run_until_stopped().unwrap();
+        assert_eq!(machine.halt_reason(), Some(HaltReason::Halted));
+        machine.reset(&program);
+        assert_eq!(machine.halt_reason(), None);
+    }
+
+    #[test]
+    fn test_extend_ascii() {
+        let mut machine = Machine::new(&[99]);
+        machine.extend_ascii("AB");
+        machine.extend_ascii_line("C");
+        machine.extend_ascii_line("");
+        assert_eq!(machine.inputs, [65, 66, 67, 10, 10]);
+    }
+
+    #[test_case("99" => vec![0]; "halt")]
+    #[test_case("1,0,0,0,99,7,7" => vec![0, 4]; "data after halt")]
+    #[test_case("1105,1,5,7,7,99" => vec![0, 5]; "always jumps")]
+    #[test_case("1106,1,5,99,7,99" => vec![0, 3]; "never jumps")]
+    #[test_case("1005,9,6,99,7,7,104,0,99,0" => vec![0, 3, 6, 8]; "both branches")]
+    #[test_case("5,9,10,99,7,7,104,0,99,0,6" => vec![0, 3]; "target in memory")]
+    #[test_case("1105,1,-1" => vec![0]; "jump out of the program")]
+    fn test_reachable_addresses(input: &str) -> Vec<usize> {
+        let program = parse_program(input).unwrap();
+        let mut addresses = reachable_addresses(&program)
+            .into_iter()
+            .collect::<Vec<_>>();
+        addresses.sort_unstable();
+        addresses
+    }
+
+    #[test]
+    fn test_coverage() {
+        // Outputs the input if it is non-zero
+        let program = parse_program("3,11,1006,11,9,4,11,99,7,99,1,0").unwrap();
+        let mut machine = Machine::new(&program);
+        machine.track_coverage = true;
+        machine.inputs.push_back(0);
+        machine.run_until_stopped().unwrap();
+        assert_eq!(machine.coverage(), &HashSet::from([0, 2, 9]));
+
+        machine.reset(&program);
+        assert!(machine.coverage().is_empty());
+        machine.inputs.push_back(5);
+        machine.run_until_stopped().unwrap();
+        assert_eq!(machine.coverage(), &HashSet::from([0, 2, 5, 7]));
+
+        let mut unused = reachable_addresses(&program)
+            .into_iter()
+            .map(|address| Value::try_from(address).unwrap())
+            .filter(|address| !machine.coverage().contains(address))
+            .collect::<Vec<_>>();
+        unused.sort_unstable();
+        assert_eq!(unused, [9]);
+    }
+
+    #[test]
+    fn test_coverage_disabled() {
+        let mut machine = Machine::new(&[104, 1, 99]);
+        machine.run_until_stopped().unwrap();
+        assert!(machine.coverage().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_memory() {
+        let path = temp_path("memory");
+        // Counts up in address 9, and outputs every count
+        let program = parse_program("1001,9,1,9,4,9,1105,1,0,0").unwrap();
+        let mut machine = Machine::new(&program);
+        machine.run_until_output().unwrap();
+        machine.run_until_output().unwrap();
+        machine.save_memory(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "1001,9,1,9,4,9,1105,1,0,2"
+        );
+
+        let mut restored = Machine::new(&[]);
+        restored.load_memory(&path).unwrap();
+        assert_eq!(restored.run_until_output().unwrap(), Some(3));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_memory_errors() {
+        let path = temp_path("invalid");
+        fs::write(&path, "1,2,three").unwrap();
+        let mut machine = Machine::new(&[99]);
+        let err = machine.load_memory(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+
+        let err = machine.load_memory(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(machine, Machine::new(&[99]));
+    }
+
+    #[test]
+    fn test_scheduler_feedback_loop() {
+        // Day 7 example: amplifiers in a feedback loop, which halt after five passes
+        let program = parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+            27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        )
+        .unwrap();
+        let machines = [9, 8, 7, 6, 5]
+            .into_iter()
+            .map(|phase| {
+                let mut machine = Machine::new(&program);
+                machine.inputs.push_back(phase);
+                machine
+            })
+            .collect::<Vec<_>>();
+        let mut scheduler = Scheduler::new(machines);
+        scheduler.machines[0].inputs.push_back(0);
+        let mut thruster = Vec::new();
+        let reason = scheduler
+            .run(&mut |ix, value| {
+                if ix == 4 {
+                    thruster.push(value);
+                }
+                Some((ix + 1) % 5)
+            })
+            .unwrap();
+        assert_eq!(reason, HaltReason::Halted);
+        assert_eq!(thruster.len(), 5);
+        assert_eq!(thruster.last(), Some(&139_629_729));
+    }
+
+    #[test]
+    fn test_scheduler_idle() {
+        // Adds one to every input and passes it on, forever
+        let program = parse_program("3,9,1001,9,1,9,4,9,1105,1,0").unwrap();
+        let mut scheduler = Scheduler::new(vec![Machine::new(&program), Machine::new(&program)]);
+        scheduler.machines[0].inputs.push_back(0);
+        let mut seen = Vec::new();
+        let reason = scheduler
+            .run(&mut |ix, value| {
+                seen.push(value);
+                (value < 5).then_some(1 - ix)
+            })
+            .unwrap();
+        assert_eq!(reason, HaltReason::BlockedOnInput);
+        assert_eq!(seen, [1, 2, 3, 4, 5]);
+        assert!(
+            scheduler
+                .machines
+                .iter()
+                .all(|machine| machine.state() == State::Running)
+        );
+    }
+
+    /// Collects every packet, and drops it.
+    struct Collect(Vec<Vec<Value>>);
+
+    impl Router for Collect {
+        fn packet_size(&self) -> usize {
+            2
+        }
+
+        fn route<'a>(&mut self, _: usize, packet: &'a [Value]) -> Option<(usize, &'a [Value])> {
+            self.0.push(packet.to_vec());
+            None
+        }
+    }
+
+    #[test]
+    fn test_scheduler_packets() {
+        // Echoes every input
+        let program = parse_program("3,7,4,7,1105,1,0").unwrap();
+        let mut machine = Machine::new(&program);
+        machine.inputs.extend([1, 2, 3]);
+        let mut scheduler = Scheduler::new(vec![machine]);
+        let mut router = Collect(Vec::new());
+        let reason = scheduler.run(&mut router).unwrap();
+        assert_eq!(reason, HaltReason::BlockedOnInput);
+        assert_eq!(router.0, [[1, 2]]);
+        assert_eq!(scheduler.machines[0].outputs, [3]);
+    }
+
+    #[test_case("1,x,3"; "not a number")]
+    #[test_case("1 2,3"; "missing comma")]
+    fn test_parse_program_errors(input: &str) {
+        assert!(parse_program(input).is_err());
+    }
 }
@@ -0,0 +1,135 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+//! Runs a single day without `cargo-aoc`:
+//!
+//! ```sh
+//! cargo run -- --day 1 --part 2 --input input/2019/day1.txt
+//! ```
+
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use advent_of_code_2019::runner::run_day;
+use thiserror::Error;
+
+const USAGE: &str = "Usage: advent-of-code-2019 --day N --part P --input FILE [--trace]";
+
+#[derive(Debug, Error)]
+enum ArgsError {
+    #[error("Missing value for {0}")]
+    MissingValue(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidNumber(String, ParseIntError),
+    #[error("Unknown argument: {0}")]
+    UnknownArgument(String),
+    #[error("Missing required argument {0}")]
+    MissingArgument(&'static str),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    day: u8,
+    part: u8,
+    input: PathBuf,
+    /// Log every instruction executed by the Intcode machines.
+    trace: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, ArgsError> {
+    let mut day = None;
+    let mut part = None;
+    let mut input = None;
+    let mut trace = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--day" | "--part" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ArgsError::MissingValue(arg.clone()))?;
+                let number = value
+                    .parse()
+                    .map_err(|err| ArgsError::InvalidNumber(arg.clone(), err))?;
+                if arg == "--day" {
+                    day = Some(number);
+                } else {
+                    part = Some(number);
+                }
+            }
+            "--input" => {
+                input = Some(args.next().ok_or(ArgsError::MissingValue(arg))?.into());
+            }
+            "--trace" => trace = true,
+            _ => return Err(ArgsError::UnknownArgument(arg)),
+        }
+    }
+    Ok(Args {
+        day: day.ok_or(ArgsError::MissingArgument("--day"))?,
+        part: part.ok_or(ArgsError::MissingArgument("--part"))?,
+        input: input.ok_or(ArgsError::MissingArgument("--input"))?,
+        trace,
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match run_day(args.day, args.part, &args.input, args.trace) {
+        Ok(answer) => {
+            println!("{answer}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+        parse_args(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&[
+            "--input", "day9.txt", "--trace", "--day", "9", "--part", "2",
+        ])
+        .unwrap();
+        assert_eq!(
+            args,
+            Args {
+                day: 9,
+                part: 2,
+                input: "day9.txt".into(),
+                trace: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(matches!(
+            parse(&["--day", "1", "--part", "1"]),
+            Err(ArgsError::MissingArgument("--input"))
+        ));
+        assert!(matches!(parse(&["--day"]), Err(ArgsError::MissingValue(_))));
+        assert!(matches!(
+            parse(&["--day", "one"]),
+            Err(ArgsError::InvalidNumber(..))
+        ));
+        assert!(matches!(
+            parse(&["--verbose"]),
+            Err(ArgsError::UnknownArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! Drawing grids as text in the terminal, with two rows of cells on each line of text. Every line
+//! starts with a newline, so that the image does not share its first line with other output.
+
+use std::fmt::Display;
+
+use crate::geom::{Position, SparseGrid};
+
+const RESET: &str = "\x1b[0m";
+
+/// The eight basic ANSI terminal colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+#[allow(unused, reason = "full palette")]
+pub enum Color {
+    #[default]
+    Black = 0,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Purple,
+    Cyan,
+    White,
+}
+
+/// Cells from `min` to `max`, two rows at a time. The upper row comes first in each pair, and the
+/// lower row is `None` below the last row.
+fn row_pairs<T: Default + Clone>(
+    grid: &SparseGrid<T>,
+    min: Position,
+    max: Position,
+) -> impl Iterator<Item = Vec<(T, Option<T>)>> {
+    (min.y..=max.y).step_by(2).map(move |y| {
+        (min.x..=max.x)
+            .map(|x| {
+                let upper = grid.get(Position::new(x, y));
+                let lower = (y < max.y).then(|| grid.get(Position::new(x, y + 1)));
+                (upper, lower)
+            })
+            .collect()
+    })
+}
+
+/// The block character with the given halves lit.
+const fn half_block(upper: bool, lower: bool) -> char {
+    match (upper, lower) {
+        (true, true) => '█',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (false, false) => ' ',
+    }
+}
+
+/// Draws the cells where `lit` is true with block characters, and the rest as spaces.
+pub fn monochrome<T: Default + Clone>(grid: &SparseGrid<T>, lit: impl Fn(&T) -> bool) -> String {
+    let Some((min, max)) = grid.bounds() else {
+        return String::new();
+    };
+    let mut image = String::new();
+    for row in row_pairs(grid, min, max) {
+        image.push('\n');
+        for (upper, lower) in row {
+            let lower = lower.is_some_and(|lower| lit(&lower));
+            image.push(half_block(lit(&upper), lower));
+        }
+    }
+    image
+}
+
+/// Like `monochrome`, for cells stored one row after another in a slice, `width` cells per row.
+pub fn monochrome_rows<T>(cells: &[T], width: usize, lit: impl Fn(&T) -> bool) -> String {
+    let rows = cells.chunks_exact(width).collect::<Vec<_>>();
+    let mut image = String::new();
+    for pair in rows.chunks(2) {
+        image.push('\n');
+        for (x, upper) in pair[0].iter().enumerate() {
+            let lower = pair.get(1).is_some_and(|lower| lit(&lower[x]));
+            image.push(half_block(lit(upper), lower));
+        }
+    }
+    image
+}
+
+/// Displays a grid of colors, using the foreground color for the upper half of each character and
+/// the background color for the lower half.
+pub struct AnsiGrid<'a> {
+    grid: &'a SparseGrid<Color>,
+    area: Option<(Position, Position)>,
+}
+
+impl<'a> AnsiGrid<'a> {
+    /// Shows every cell that has been set.
+    pub fn new(grid: &'a SparseGrid<Color>) -> Self {
+        Self {
+            grid,
+            area: grid.bounds(),
+        }
+    }
+
+    /// Shows the cells from `min` to `max`, both included.
+    #[allow(unused, reason = "tests")]
+    pub const fn with_area(grid: &'a SparseGrid<Color>, min: Position, max: Position) -> Self {
+        Self {
+            grid,
+            area: Some((min, max)),
+        }
+    }
+}
+
+impl Display for AnsiGrid<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some((min, max)) = self.area else {
+            return Ok(());
+        };
+        for row in row_pairs(self.grid, min, max) {
+            writeln!(f)?;
+            for (upper, lower) in row {
+                let fg = upper as u8;
+                let bg = lower.unwrap_or_default() as u8;
+                write!(f, "\x1b[3{fg}m\x1b[4{bg}m▀")?;
+            }
+            f.write_str(RESET)?;
+        }
+        Ok(())
+    }
+}
+
+/// A rectangle of cells that can be turned into an image.
+#[cfg(feature = "rgba")]
+pub trait Raster {
+    type Cell;
+
+    /// Width and height, in cells.
+    fn size(&self) -> (usize, usize);
+
+    /// Every cell, one row at a time from the top-left corner.
+    fn cells(&self) -> impl Iterator<Item = Self::Cell>;
+}
+
+/// The cells from the top-left to the bottom-right corner of the cells that have been set.
+#[cfg(feature = "rgba")]
+impl<T: Default + Clone> Raster for SparseGrid<T> {
+    type Cell = T;
+
+    fn size(&self) -> (usize, usize) {
+        self.bounds().map_or((0, 0), |(min, max)| {
+            ((min.x..=max.x).count(), (min.y..=max.y).count())
+        })
+    }
+
+    fn cells(&self) -> impl Iterator<Item = T> {
+        self.bounds().into_iter().flat_map(move |(min, max)| {
+            (min.y..=max.y)
+                .flat_map(move |y| (min.x..=max.x).map(move |x| self.get(Position::new(x, y))))
+        })
+    }
+}
+
+/// Raw RGBA pixels for the cells of `raster`, one pixel per cell and four bytes per pixel, along
+/// with the width and height of the image. Writing the buffer to an image file is left to the
+/// caller.
+#[cfg(feature = "rgba")]
+pub fn to_rgba<R: Raster>(
+    raster: &R,
+    palette: impl Fn(&R::Cell) -> [u8; 4],
+) -> (Vec<u8>, usize, usize) {
+    let (width, height) = raster.size();
+    let pixels = raster.cells().flat_map(|cell| palette(&cell)).collect();
+    (pixels, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> SparseGrid<Color> {
+        let mut grid = SparseGrid::new();
+        grid.set(Position::new(0, 0), Color::Red);
+        grid.set(Position::new(1, 1), Color::Blue);
+        grid.set(Position::new(1, 2), Color::White);
+        grid
+    }
+
+    #[test]
+    fn test_monochrome() {
+        let image = monochrome(&example(), |&color| color != Color::Black);
+        assert_eq!(image, "\n▀▄\n ▀");
+    }
+
+    #[test]
+    fn test_monochrome_rows() {
+        let image = monochrome_rows(b"100011", 2, |&cell| cell == b'1');
+        assert_eq!(image, "\n▀ \n▀▀");
+        assert_eq!(monochrome_rows::<u8>(&[], 2, |_| true), "");
+    }
+
+    #[test]
+    fn test_ansi_grid() {
+        let text = AnsiGrid::new(&example()).to_string();
+        assert_eq!(
+            text,
+            "\n\x1b[31m\x1b[40m▀\x1b[30m\x1b[44m▀\x1b[0m\
+            \n\x1b[30m\x1b[40m▀\x1b[37m\x1b[40m▀\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_ansi_grid_area() {
+        let grid = example();
+        let text = AnsiGrid::with_area(&grid, Position::new(1, 1), Position::new(1, 1)).to_string();
+        assert_eq!(text, "\n\x1b[34m\x1b[40m▀\x1b[0m");
+    }
+
+    #[cfg(feature = "rgba")]
+    #[test]
+    fn test_to_rgba() {
+        let palette = |&color: &Color| match color {
+            Color::Black => [0, 0, 0, 255],
+            Color::Red => [255, 0, 0, 255],
+            Color::Blue => [0, 0, 255, 255],
+            _ => [255, 255, 255, 255],
+        };
+        let (pixels, width, height) = to_rgba(&example(), palette);
+        assert_eq!((width, height), (2, 3));
+        assert_eq!(
+            pixels,
+            [
+                [255, 0, 0, 255],
+                [0, 0, 0, 255],
+                [0, 0, 0, 255],
+                [0, 0, 255, 255],
+                [0, 0, 0, 255],
+                [255, 255, 255, 255],
+            ]
+            .concat()
+        );
+    }
+
+    #[cfg(feature = "rgba")]
+    #[test]
+    fn test_to_rgba_empty() {
+        let (pixels, width, height) = to_rgba(&SparseGrid::<Color>::new(), |_| [0; 4]);
+        assert_eq!((pixels.len(), width, height), (0, 0, 0));
+    }
+}
@@ -0,0 +1,86 @@
+//! Runs a single day on an input file, without the `cargo-aoc` harness.
+
+use std::path::Path;
+use std::{fs, io};
+
+use thiserror::Error;
+
+use crate::solver::solver;
+
+#[derive(Debug, Error)]
+pub enum RunError {
+    #[error("No solution for day {0}")]
+    UnknownDay(u8),
+    #[error("Each day has part 1 and 2, not {0}")]
+    UnknownPart(u8),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Solves `part` of `day` for the input in `input_path`. Trailing whitespace in the file, like
+/// the final newline, is ignored. With `trace`, the Intcode machines log each instruction to
+/// stderr.
+///
+/// # Errors
+///
+/// Fails if there is no such day or part, or if the file can not be read.
+pub fn run_day(day: u8, part: u8, input_path: &Path, trace: bool) -> Result<String, RunError> {
+    let mut solver = solver(day).ok_or(RunError::UnknownDay(day))?;
+    solver.set_trace(trace);
+    if !matches!(part, 1 | 2) {
+        return Err(RunError::UnknownPart(part));
+    }
+    let input = fs::read_to_string(input_path)?;
+    let input = input.trim_end();
+    Ok(if part == 1 {
+        solver.part1(input)
+    } else {
+        solver.part2(input)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::{env, process};
+
+    use super::*;
+
+    /// A path in the temporary directory, unique to this test process.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("aoc-2019-runner-{}-{name}.txt", process::id()))
+    }
+
+    fn input_file(name: &str, contents: &str) -> PathBuf {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_run_day() {
+        let path = input_file("day-1", "12\n14\n1969\n");
+        assert_eq!(run_day(1, 1, &path, false).unwrap(), "658");
+        assert_eq!(run_day(1, 2, &path, false).unwrap(), "970");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let path = input_file("errors", "12\n");
+        assert!(matches!(
+            run_day(26, 1, &path, false),
+            Err(RunError::UnknownDay(26))
+        ));
+        assert!(matches!(
+            run_day(1, 3, &path, false),
+            Err(RunError::UnknownPart(3))
+        ));
+        fs::remove_file(&path).unwrap();
+        let missing = temp_path("missing");
+        assert!(matches!(
+            run_day(1, 1, &missing, false),
+            Err(RunError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+//! A uniform entry point for every day, independent of the `cargo-aoc` harness.
+
+use std::fmt::Display;
+
+use crate::{
+    day_01, day_02, day_03, day_04, day_05, day_06, day_07, day_08, day_09, day_10, day_11, day_12,
+    day_13, day_14, day_15, day_16, day_17, day_18, day_19, day_20, day_21, day_22, day_23, day_24,
+    day_25,
+};
+
+pub trait Solver {
+    fn part1(&self, input: &str) -> String;
+    fn part2(&self, input: &str) -> String;
+
+    /// Makes the Intcode machines of later calls log every instruction they execute. Days without
+    /// Intcode ignore it.
+    fn set_trace(&mut self, _enabled: bool) {}
+}
+
+/// Turns the return value of a `part_N` function into the answer to print.
+pub trait Answer {
+    fn into_answer(self) -> String;
+}
+
+macro_rules! impl_answer {
+    ($($ty:ty),*) => {
+        $(
+            impl Answer for $ty {
+                fn into_answer(self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_answer!(i32, i64, u32, u64, usize, String);
+
+impl<T: Answer> Answer for Option<T> {
+    fn into_answer(self) -> String {
+        self.map_or_else(|| "No answer".to_string(), Answer::into_answer)
+    }
+}
+
+impl<T: Answer, E: Display> Answer for Result<T, E> {
+    fn into_answer(self) -> String {
+        self.map_or_else(|err| format!("Error: {err}"), Answer::into_answer)
+    }
+}
+
+/// The solver for `day`, or `None` if there is no such day.
+#[must_use]
+pub fn solver(day: u8) -> Option<Box<dyn Solver>> {
+    Some(match day {
+        1 => Box::new(day_01::Day01),
+        2 => Box::new(day_02::Day02::default()),
+        3 => Box::new(day_03::Day03),
+        4 => Box::new(day_04::Day04),
+        5 => Box::new(day_05::Day05::default()),
+        6 => Box::new(day_06::Day06),
+        7 => Box::new(day_07::Day07::default()),
+        8 => Box::new(day_08::Day08),
+        9 => Box::new(day_09::Day09::default()),
+        10 => Box::new(day_10::Day10),
+        11 => Box::new(day_11::Day11::default()),
+        12 => Box::new(day_12::Day12),
+        13 => Box::new(day_13::Day13::default()),
+        14 => Box::new(day_14::Day14),
+        15 => Box::new(day_15::Day15::default()),
+        16 => Box::new(day_16::Day16),
+        17 => Box::new(day_17::Day17::default()),
+        18 => Box::new(day_18::Day18),
+        19 => Box::new(day_19::Day19::default()),
+        20 => Box::new(day_20::Day20),
+        21 => Box::new(day_21::Day21::default()),
+        22 => Box::new(day_22::Day22),
+        23 => Box::new(day_23::Day23::default()),
+        24 => Box::new(day_24::Day24),
+        25 => Box::new(day_25::Day25::default()),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0; "before the first day")]
+    #[test_case(26; "after the last day")]
+    fn test_unknown_day(day: u8) {
+        assert!(solver(day).is_none());
+    }
+
+    #[test]
+    fn test_day_1() {
+        let solver = solver(1).unwrap();
+        assert_eq!(solver.part1("12\n14\n1969"), "658");
+        assert_eq!(solver.part2("12\n14\n1969"), "970");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let solver = solver(1).unwrap();
+        assert_eq!(
+            solver.part1("12\nfourteen"),
+            "Error: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Some(42_u64).into_answer(), "42");
+        assert_eq!(None::<u64>.into_answer(), "No answer");
+        assert_eq!(Ok::<_, String>(Some(-1_i64)).into_answer(), "-1");
+    }
+}